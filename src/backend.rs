@@ -0,0 +1,285 @@
+use std::mem::MaybeUninit;
+use std::ptr::{self, NonNull};
+use x11::xlib::{
+    CWBackPixel, CWBackPixmap, CWBorderPixel, CopyFromParent, CurrentTime, Display,
+    RevertToPointerRoot, Window, XConfigureWindow, XCreateWindow, XDestroyWindow, XMapWindow,
+    XMoveResizeWindow, XMoveWindow, XRaiseWindow, XReparentWindow, XSetInputFocus,
+    XSetWindowAttributes, XUnmapWindow, XWindowChanges,
+};
+
+/// The X operations the window management logic depends on, so that logic can be exercised
+/// without an X server
+pub trait XBackend {
+    #[allow(clippy::too_many_arguments)]
+    fn create_frame(
+        &self,
+        parent: Window,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        border_width: u32,
+        border_color: u64,
+        background_color: Option<u64>,
+    ) -> Window;
+    fn destroy(&self, w: Window);
+    fn reparent(&self, w: Window, parent: Window, x: i32, y: i32);
+    fn map(&self, w: Window);
+    fn unmap(&self, w: Window);
+    fn move_window(&self, w: Window, x: i32, y: i32);
+    fn move_resize(&self, w: Window, x: i32, y: i32, width: u32, height: u32);
+    fn configure(&self, w: Window, value_mask: u32, changes: XWindowChanges);
+    fn raise(&self, w: Window);
+    fn focus(&self, w: Window);
+}
+
+pub struct XlibBackend {
+    display: NonNull<Display>,
+}
+
+impl XlibBackend {
+    /// The display is borrowed, closing it is left to the owner
+    pub fn new(display: NonNull<Display>) -> Self {
+        XlibBackend { display }
+    }
+}
+
+impl XBackend for XlibBackend {
+    #[allow(clippy::too_many_arguments)]
+    fn create_frame(
+        &self,
+        parent: Window,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        border_width: u32,
+        border_color: u64,
+        background_color: Option<u64>,
+    ) -> Window {
+        unsafe {
+            let mut attributes: XSetWindowAttributes = MaybeUninit::zeroed().assume_init();
+            attributes.border_pixel = border_color;
+            // Without a background the window isn't cleared before the client draws into it
+            let mut value_mask = CWBorderPixel;
+            match background_color {
+                Some(color) => {
+                    attributes.background_pixel = color;
+                    value_mask |= CWBackPixel;
+                }
+                None => value_mask |= CWBackPixmap,
+            }
+            XCreateWindow(
+                self.display.as_ptr(),
+                parent,
+                x,
+                y,
+                width,
+                height,
+                border_width,
+                CopyFromParent,
+                CopyFromParent as u32,
+                ptr::null_mut(),
+                value_mask,
+                &mut attributes,
+            )
+        }
+    }
+
+    fn destroy(&self, w: Window) {
+        unsafe {
+            XDestroyWindow(self.display.as_ptr(), w);
+        }
+    }
+
+    fn reparent(&self, w: Window, parent: Window, x: i32, y: i32) {
+        unsafe {
+            XReparentWindow(self.display.as_ptr(), w, parent, x, y);
+        }
+    }
+
+    fn map(&self, w: Window) {
+        unsafe {
+            XMapWindow(self.display.as_ptr(), w);
+        }
+    }
+
+    fn unmap(&self, w: Window) {
+        unsafe {
+            XUnmapWindow(self.display.as_ptr(), w);
+        }
+    }
+
+    fn move_window(&self, w: Window, x: i32, y: i32) {
+        unsafe {
+            XMoveWindow(self.display.as_ptr(), w, x, y);
+        }
+    }
+
+    fn move_resize(&self, w: Window, x: i32, y: i32, width: u32, height: u32) {
+        unsafe {
+            XMoveResizeWindow(self.display.as_ptr(), w, x, y, width, height);
+        }
+    }
+
+    fn configure(&self, w: Window, value_mask: u32, mut changes: XWindowChanges) {
+        unsafe {
+            XConfigureWindow(self.display.as_ptr(), w, value_mask, &mut changes);
+        }
+    }
+
+    fn raise(&self, w: Window) {
+        unsafe {
+            XRaiseWindow(self.display.as_ptr(), w);
+        }
+    }
+
+    fn focus(&self, w: Window) {
+        unsafe {
+            XSetInputFocus(self.display.as_ptr(), w, RevertToPointerRoot, CurrentTime);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::XBackend;
+    use std::cell::{Cell, RefCell};
+    use x11::xlib::{Window, XWindowChanges};
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Call {
+        CreateFrame {
+            parent: Window,
+            x: i32,
+            y: i32,
+            width: u32,
+            height: u32,
+            border_width: u32,
+        },
+        Destroy(Window),
+        Reparent {
+            w: Window,
+            parent: Window,
+            x: i32,
+            y: i32,
+        },
+        Map(Window),
+        Unmap(Window),
+        MoveWindow {
+            w: Window,
+            x: i32,
+            y: i32,
+        },
+        MoveResize {
+            w: Window,
+            x: i32,
+            y: i32,
+            width: u32,
+            height: u32,
+        },
+        Configure {
+            w: Window,
+            value_mask: u32,
+            changes: XWindowChanges,
+        },
+        Raise(Window),
+        Focus(Window),
+    }
+
+    /// Records every call instead of talking to an X server
+    pub struct MockBackend {
+        calls: RefCell<Vec<Call>>,
+        next_window: Cell<Window>,
+    }
+
+    impl MockBackend {
+        pub fn new() -> Self {
+            MockBackend {
+                calls: RefCell::new(Vec::new()),
+                next_window: Cell::new(1000),
+            }
+        }
+
+        pub fn calls(&self) -> Vec<Call> {
+            self.calls.borrow().clone()
+        }
+
+        fn record(&self, call: Call) {
+            self.calls.borrow_mut().push(call);
+        }
+    }
+
+    impl XBackend for MockBackend {
+        #[allow(clippy::too_many_arguments)]
+        fn create_frame(
+            &self,
+            parent: Window,
+            x: i32,
+            y: i32,
+            width: u32,
+            height: u32,
+            border_width: u32,
+            _border_color: u64,
+            _background_color: Option<u64>,
+        ) -> Window {
+            self.record(Call::CreateFrame {
+                parent,
+                x,
+                y,
+                width,
+                height,
+                border_width,
+            });
+            let frame = self.next_window.get();
+            self.next_window.set(frame + 1);
+            frame
+        }
+
+        fn destroy(&self, w: Window) {
+            self.record(Call::Destroy(w));
+        }
+
+        fn reparent(&self, w: Window, parent: Window, x: i32, y: i32) {
+            self.record(Call::Reparent { w, parent, x, y });
+        }
+
+        fn map(&self, w: Window) {
+            self.record(Call::Map(w));
+        }
+
+        fn unmap(&self, w: Window) {
+            self.record(Call::Unmap(w));
+        }
+
+        fn move_window(&self, w: Window, x: i32, y: i32) {
+            self.record(Call::MoveWindow { w, x, y });
+        }
+
+        fn move_resize(&self, w: Window, x: i32, y: i32, width: u32, height: u32) {
+            self.record(Call::MoveResize {
+                w,
+                x,
+                y,
+                width,
+                height,
+            });
+        }
+
+        fn configure(&self, w: Window, value_mask: u32, changes: XWindowChanges) {
+            self.record(Call::Configure {
+                w,
+                value_mask,
+                changes,
+            });
+        }
+
+        fn raise(&self, w: Window) {
+            self.record(Call::Raise(w));
+        }
+
+        fn focus(&self, w: Window) {
+            self.record(Call::Focus(w));
+        }
+    }
+}
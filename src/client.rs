@@ -0,0 +1,259 @@
+use crate::layout::Geometry;
+use x11::xlib::Window;
+
+#[derive(Debug)]
+pub struct Client {
+    pub window: Window,
+    pub frame: Window,
+    /// Set while the client is iconified; hidden clients are neither tiled nor focusable
+    pub hidden: bool,
+    /// Set while the client is put out of the way of the desktop shown with Mod+Shift+D, which
+    /// keeps it from being tiled or focused like `hidden` without counting as iconified
+    pub desktop_hidden: bool,
+    /// Number of UnmapNotify events for this client caused by the window manager itself
+    pub ignore_unmaps: u32,
+    pub workspace: usize,
+    /// Floating clients keep their own geometry instead of being tiled
+    pub floating: bool,
+    /// Sticky clients are shown on every workspace
+    pub sticky: bool,
+    /// Cleared for clients that asked not to be decorated through _MOTIF_WM_HINTS
+    pub decorated: bool,
+    /// Set while the client's WM_HINTS ask for attention and it hasn't been focused since
+    pub urgent: bool,
+    /// Always on top clients are raised over the others whenever the stacking order changes
+    pub above: bool,
+    pub maximized_horz: bool,
+    pub maximized_vert: bool,
+    /// Frame geometry to go back to when a maximized client is restored
+    pub restore_geometry: Option<Geometry>,
+    /// Frame geometry the client had when it was last floating, restored when it floats again
+    pub float_geometry: Option<Geometry>,
+    /// Process owning the window, if it set _NET_WM_PID
+    pub pid: Option<u32>,
+    /// The terminal this client was started from and took the place of
+    pub swallowing: Option<Window>,
+    /// Set on a terminal while a client started from it takes its place
+    pub swallowed: bool,
+    /// Opacity while focused, from 0 for transparent to 1 for opaque
+    pub opacity: f32,
+    /// The window this dialog belongs to, from WM_TRANSIENT_FOR
+    pub transient_for: Option<Window>,
+    /// Share of the stack's height relative to the other stacked clients
+    pub size_factor: f32,
+    /// Set for clients whose WM_CLASS is configured to never take the focus
+    pub no_focus: bool,
+    /// Frame width, height and border width the rounded corners were last cut for
+    pub shaped_size: Option<(u32, u32, u32)>,
+}
+
+impl Client {
+    pub fn new(window: Window, frame: Window, workspace: usize) -> Self {
+        Client {
+            window,
+            frame,
+            hidden: false,
+            desktop_hidden: false,
+            ignore_unmaps: 0,
+            workspace,
+            floating: false,
+            sticky: false,
+            decorated: true,
+            urgent: false,
+            above: false,
+            maximized_horz: false,
+            maximized_vert: false,
+            restore_geometry: None,
+            float_geometry: None,
+            pid: None,
+            swallowing: None,
+            swallowed: false,
+            opacity: 1.0,
+            transient_for: None,
+            size_factor: 1.0,
+            no_focus: false,
+            shaped_size: None,
+        }
+    }
+
+    pub fn on_workspace(&self, workspace: usize) -> bool {
+        self.sticky || self.workspace == workspace
+    }
+
+    /// Whether the client's frame is mapped while its workspace is viewed
+    pub fn shown(&self) -> bool {
+        !self.hidden && !self.desktop_hidden
+    }
+}
+
+#[derive(Debug)]
+pub struct ClientList(Vec<Client>);
+
+impl ClientList {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// A list of clients on the first workspace, one per (window, frame) pair
+    #[cfg(test)]
+    pub fn from_windows(windows: &[(Window, Window)]) -> Self {
+        Self(windows.iter().map(|&(w, f)| Client::new(w, f, 0)).collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn contains(&self, w: &Window) -> bool {
+        self.0.iter().any(|c| c.window == *w)
+    }
+
+    pub fn find(&self, w: &Window) -> Option<usize> {
+        self.0.iter().position(|c| c.window == *w)
+    }
+
+    pub fn index(&self, i: usize) -> Option<&Client> {
+        self.0.get(i)
+    }
+
+    pub fn get(&self, w: &Window) -> Option<&Window> {
+        self.client(w).map(|c| &c.frame)
+    }
+
+    pub fn client(&self, w: &Window) -> Option<&Client> {
+        self.0.iter().find(|c| c.window == *w)
+    }
+
+    pub fn client_mut(&mut self, w: &Window) -> Option<&mut Client> {
+        self.0.iter_mut().find(|c| c.window == *w)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Client> {
+        self.0.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut Client> {
+        self.0.iter_mut()
+    }
+
+    /// Clients that are currently shown on `workspace`
+    pub fn visible(&self, workspace: usize) -> impl DoubleEndedIterator<Item = &Client> {
+        self.0
+            .iter()
+            .filter(move |c| c.shown() && c.on_workspace(workspace))
+    }
+
+    pub fn insert(&mut self, client: Client) {
+        self.0.push(client);
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.0.swap(a, b);
+    }
+
+    /// Moves the client at `i` to the front, keeping the order of the others
+    pub fn move_to_front(&mut self, i: usize) {
+        let client = self.0.remove(i);
+        self.0.insert(0, client);
+    }
+
+    /// Rotates the clients at `indices` left by `n`, so the one at `indices[n]` ends up at
+    /// `indices[0]`, leaving the clients in between in place
+    pub fn rotate_left(&mut self, indices: &[usize], n: usize) {
+        if indices.is_empty() {
+            return;
+        }
+        let mut sources = indices.to_vec();
+        sources.rotate_left(n % indices.len());
+
+        let mut slots: Vec<Option<Client>> = self.0.drain(..).map(Some).collect();
+        let mut clients = Vec::with_capacity(slots.len());
+        for i in 0..slots.len() {
+            let source = indices
+                .iter()
+                .position(|&index| index == i)
+                .map_or(i, |k| sources[k]);
+            clients.push(slots[source].take().unwrap());
+        }
+        self.0 = clients;
+    }
+
+    pub fn remove(&mut self, w: &Window) {
+        if let Some(i) = self.find(w) {
+            self.0.remove(i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_clients_by_window() {
+        let clients = ClientList::from_windows(&[(1, 2), (3, 4)]);
+
+        assert_eq!(clients.find(&3), Some(1));
+        assert_eq!(clients.get(&3), Some(&4));
+        assert!(clients.contains(&1));
+        assert!(!clients.contains(&2));
+    }
+
+    #[test]
+    fn remove_keeps_the_order() {
+        let mut clients = ClientList::from_windows(&[(1, 2), (3, 4), (5, 6)]);
+        clients.remove(&3);
+
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients.find(&5), Some(1));
+    }
+
+    #[test]
+    fn swap_reorders_clients() {
+        let mut clients = ClientList::from_windows(&[(1, 2), (3, 4)]);
+        clients.swap(0, 1);
+
+        assert_eq!(clients.index(0).unwrap().window, 3);
+    }
+
+    #[test]
+    fn move_to_front_shifts_the_others_back() {
+        let mut clients = ClientList::from_windows(&[(1, 2), (3, 4), (5, 6)]);
+        clients.move_to_front(2);
+
+        let order: Vec<Window> = clients.iter().map(|c| c.window).collect();
+        assert_eq!(order, vec![5, 1, 3]);
+    }
+
+    #[test]
+    fn rotate_left_keeps_the_cyclic_order() {
+        let mut clients = ClientList::from_windows(&[(1, 2), (3, 4), (5, 6), (7, 8), (9, 10)]);
+        // The client at index 1 isn't part of the rotation and stays put
+        clients.rotate_left(&[0, 2, 3, 4], 2);
+
+        let order: Vec<Window> = clients.iter().map(|c| c.window).collect();
+        assert_eq!(order, vec![7, 3, 9, 1, 5]);
+    }
+
+    #[test]
+    fn visible_skips_hidden_and_other_workspaces() {
+        let mut clients = ClientList::from_windows(&[(1, 2), (3, 4), (5, 6), (7, 8)]);
+        clients.client_mut(&3).unwrap().hidden = true;
+        clients.client_mut(&5).unwrap().workspace = 1;
+        clients.client_mut(&7).unwrap().workspace = 1;
+        clients.client_mut(&7).unwrap().sticky = true;
+
+        let visible: Vec<Window> = clients.visible(0).map(|c| c.window).collect();
+        assert_eq!(visible, vec![1, 7]);
+    }
+
+    #[test]
+    fn visible_skips_windows_put_away_for_the_desktop() {
+        let mut clients = ClientList::from_windows(&[(1, 2), (3, 4)]);
+        clients.client_mut(&1).unwrap().desktop_hidden = true;
+
+        let visible: Vec<Window> = clients.visible(0).map(|c| c.window).collect();
+        assert_eq!(visible, vec![3]);
+        assert!(!clients.client(&1).unwrap().hidden);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::config::{Color, Config};
+use log::warn;
+use std::ffi::CString;
+use std::mem::MaybeUninit;
+use x11::xlib::{
+    Display, DoBlue, DoGreen, DoRed, XAllocColor, XAllocNamedColor, XBlackPixel, XColor,
+    XDefaultColormap, XDefaultScreen, XFreeColors,
+};
+
+/// The pixels allocated for the configured colors, which only equal the colors' RGB values on
+/// TrueColor visuals
+pub struct Colors {
+    pub outer_border: u64,
+    pub urgent_border: u64,
+    pub stacked_border: u64,
+    pub inner_border: u64,
+    pub focused_monitor_border: u64,
+    pub frame_background: u64,
+    /// The pixels that were actually allocated, leaving out black used in place of failed ones
+    allocated: Vec<u64>,
+}
+
+impl Colors {
+    pub fn alloc(display: *mut Display, config: &Config) -> Self {
+        let mut allocated = Vec::new();
+        let mut pixel = |color: &Color| {
+            let pixel = alloc(display, color);
+            allocated.extend(pixel);
+            pixel.unwrap_or_else(|| black(display, color))
+        };
+        Colors {
+            outer_border: pixel(&config.outer_border_color),
+            urgent_border: pixel(&config.urgent_border_color),
+            stacked_border: pixel(&config.stacked_border_color),
+            inner_border: pixel(&config.inner_border_color),
+            focused_monitor_border: pixel(&config.focused_monitor_border_color),
+            frame_background: pixel(&config.frame_background_color),
+            allocated,
+        }
+    }
+
+    /// Gives the colors back to the colormap once nothing is drawn in them anymore
+    pub fn free(mut self, display: *mut Display) {
+        if self.allocated.is_empty() {
+            return;
+        }
+        unsafe {
+            XFreeColors(
+                display,
+                default_colormap(display),
+                self.allocated.as_mut_ptr(),
+                self.allocated.len() as i32,
+                0,
+            );
+        }
+    }
+}
+
+/// The pixel of `color` in the default colormap, or black if it can't be allocated
+pub fn pixel(display: *mut Display, color: &Color) -> u64 {
+    alloc(display, color).unwrap_or_else(|| black(display, color))
+}
+
+fn alloc(display: *mut Display, color: &Color) -> Option<u64> {
+    match color {
+        Color::Rgb(rgb) => alloc_rgb(display, *rgb),
+        Color::Named(name) => alloc_named(display, name),
+    }
+}
+
+fn black(display: *mut Display, color: &Color) -> u64 {
+    warn!("Failed to allocate the color {:?}, using black", color);
+    unsafe { XBlackPixel(display, XDefaultScreen(display)) }
+}
+
+fn alloc_rgb(display: *mut Display, rgb: u32) -> Option<u64> {
+    // XColor channels are 16 bits, repeating the byte spreads it over the whole range
+    let channel = |shift: u32| ((rgb >> shift) & 0xFF) as u16 * 0x101;
+    unsafe {
+        let mut color: XColor = MaybeUninit::zeroed().assume_init();
+        color.red = channel(16);
+        color.green = channel(8);
+        color.blue = channel(0);
+        color.flags = DoRed | DoGreen | DoBlue;
+        let status = XAllocColor(display, default_colormap(display), &mut color);
+        (status != 0).then_some(color.pixel)
+    }
+}
+
+/// Allocates a color given as `#RRGGBB` or by its X color name
+fn alloc_named(display: *mut Display, name: &str) -> Option<u64> {
+    let name = CString::new(name.trim()).ok()?;
+    unsafe {
+        let mut color: XColor = MaybeUninit::zeroed().assume_init();
+        let mut exact = color;
+        let status = XAllocNamedColor(
+            display,
+            default_colormap(display),
+            name.as_ptr(),
+            &mut color,
+            &mut exact,
+        );
+        (status != 0).then_some(color.pixel)
+    }
+}
+
+fn default_colormap(display: *mut Display) -> u64 {
+    unsafe { XDefaultColormap(display, XDefaultScreen(display)) }
+}
@@ -0,0 +1,603 @@
+use crate::keys::{Button, Key};
+use log::{error, info, warn};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use x11::xlib::{Button1, Button3, Mod1Mask};
+
+/// How a floating window follows the pointer while it is resized with Mod+right drag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeMode {
+    /// Resize the window on every motion event
+    Live,
+    /// Draw an outline and only resize the window when the button is released
+    Outline,
+}
+
+/// Where new floating windows are placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Placement {
+    /// In the middle of the screen
+    Center,
+    /// A step down and right of the last placed window
+    Cascade,
+    /// Where they overlap the other floating windows the least
+    Smart,
+}
+
+/// How the screen is split between the master and stack areas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitDirection {
+    /// Master area on the left and the stack on the right
+    Vertical,
+    /// Master area at the top and the stack below, for tall screens
+    Horizontal,
+}
+
+/// Which window is focused after Mod+comma or Mod+period rotated the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RotateFocus {
+    /// The window that moved into the place of the focused one
+    Position,
+    /// The focused window, wherever it moved to
+    Window,
+}
+
+/// Where new windows open, either `"current"`, `"primary"` or the index of a workspace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum NewWindowWorkspace {
+    Named(NamedWorkspace),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NamedWorkspace {
+    /// The workspace of the focused monitor
+    Current,
+    /// The workspace of the primary monitor
+    Primary,
+}
+
+/// A color written as a `0xRRGGBB` number, or as a string holding `#RRGGBB` or an X color name
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Color {
+    Rgb(u32),
+    Named(String),
+}
+
+/// A key combination running a shell command
+#[derive(Debug, Clone, Deserialize)]
+pub struct KeyBinding {
+    pub key: Key,
+    pub command: String,
+    /// WM_CLASS instance or class name the focused window must have for the binding to fire.
+    /// Other windows get the key as if it wasn't bound.
+    #[serde(default)]
+    pub when_class: Option<String>,
+}
+
+impl KeyBinding {
+    /// Whether the binding fires with a window of `class`, given as (instance, class), focused
+    pub fn applies_to(&self, class: Option<&(String, String)>) -> bool {
+        match (&self.when_class, class) {
+            (None, _) => true,
+            (Some(wanted), Some((instance, class))) => wanted == instance || wanted == class,
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// What a mouse button binding does to the window it is pressed on
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ButtonAction {
+    /// Drag the window, or the divider when pressed next to it
+    Move,
+    /// Resize a floating window by dragging its bottom right corner
+    Resize,
+    Close,
+    /// Run a shell command
+    Command(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ButtonBinding {
+    pub button: Button,
+    pub action: ButtonAction,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub workspaces: usize,
+    /// Place new floating windows according to `placement` instead of where they ask to be
+    pub center_new_floating: bool,
+    /// How many pixels of a window asking to be placed off screen are kept on screen
+    pub offscreen_margin: u32,
+    pub placement: Placement,
+    /// Place new floating windows under the pointer, takes precedence over centering them
+    pub spawn_at_pointer: bool,
+    pub outer_border_width: u32,
+    pub outer_border_color: Color,
+    /// Border color of windows asking for attention through the urgency hint
+    pub urgent_border_color: Color,
+    /// Border color of the windows in the monocle layout while there are others hidden behind the
+    /// one on top
+    pub stacked_border_color: Color,
+    /// Width of a second border between the outer border and the window, 0 disables it
+    pub inner_border_width: u32,
+    pub inner_border_color: Color,
+    /// Shown in frames around clients that haven't drawn yet, unless there is an inner border or
+    /// compositor_mode is set
+    pub frame_background_color: Color,
+    /// Only draw the outer border around windows on the focused monitor, when there are several
+    pub borders_on_focused_monitor_only: bool,
+    /// Width of the strips along the edges of the focused monitor when there are several, 0
+    /// disables them
+    pub focused_monitor_border_width: u32,
+    pub focused_monitor_border_color: Color,
+    /// How far Mod+Shift+arrows move and Mod+Ctrl+arrows resize a floating window, in pixels
+    pub keyboard_move_step: u32,
+    /// What each press of the same Mod+Shift/Ctrl+arrow soon after the last multiplies the step
+    /// by, so holding the key covers long distances quickly. 1 moves at the same step throughout.
+    pub keyboard_move_acceleration: f32,
+    /// Space left around and between tiled windows
+    pub gap: u32,
+    /// Let a lone tiled window fill the screen without gaps
+    pub smart_gaps: bool,
+    pub resize_mode: ResizeMode,
+    /// Shell commands started along with the window manager
+    pub autostart: Vec<String>,
+    /// Raise windows when they are clicked
+    pub raise_on_click: bool,
+    /// Raise windows when they are focused with the keyboard
+    pub raise_on_focus: bool,
+    /// Radius of the rounded corners of frames, 0 keeps them square
+    pub corner_radius: u32,
+    /// Color to fill the root window with, published through _XROOTPMAP_ID for pseudo-transparent
+    /// terminals. Left out, a wallpaper set by another program is kept.
+    pub wallpaper: Option<Color>,
+    /// Share of the screen width initially taken by the master window
+    pub master_ratio: f32,
+    /// How every workspace starts out split, Mod+T switches the current one to the other direction
+    pub split_direction: SplitDirection,
+    /// Let windows started from a terminal take the terminal's place until they are closed
+    pub swallow: bool,
+    /// How much Mod+bracketleft and Mod+bracketright change the opacity of a window
+    pub opacity_step: f32,
+    /// Opacity unfocused windows are dimmed by, 1 leaves them as they are
+    pub inactive_opacity: f32,
+    /// Workspace new windows open on, `"primary"`, `"current"` or a workspace index
+    pub new_window_workspace: NewWindowWorkspace,
+    /// Index of the monitor to treat as the primary one, in the order RandR lists them, instead of
+    /// the one RandR reports. Ignored if there is no such monitor.
+    pub primary_monitor: Option<usize>,
+    /// Extra bindings, checked before the built in ones
+    pub keys: Vec<KeyBinding>,
+    /// Mouse bindings on client windows, replacing the default Mod+left drag to move and Mod+right
+    /// drag to resize
+    pub buttons: Vec<ButtonBinding>,
+    /// How far the pointer has to travel, in pixels, before a drag moves or resizes anything
+    pub drag_threshold: u32,
+    /// How close, in pixels, a dragged floating window has to come to a screen or window edge to
+    /// snap to it, 0 disables snapping. Holding Shift while dragging doesn't snap.
+    pub snap_distance: u32,
+    pub rotate_focus: RotateFocus,
+    /// Show the windows being cycled through with Mod+Tab and only focus the chosen one once Mod
+    /// is released
+    pub window_switcher: bool,
+    /// Run through `sh` by Mod+W with a numbered line per window on its stdin, and expected to
+    /// print the chosen line, like dmenu
+    pub window_menu: String,
+    /// Focus and raise new windows, unless they set a _NET_WM_USER_TIME of 0
+    pub focus_new_windows: bool,
+    /// Create frames without a background fill, which would flash before the client draws and
+    /// gets in the way of a compositor
+    pub compositor_mode: bool,
+    /// WM_CLASS instance or class names of windows that are shown but never focused, like
+    /// notifications
+    pub no_focus_classes: Vec<String>,
+    /// WM_CLASS instance or class names of windows that manage themselves, like some games and
+    /// screen recorders. They are mapped as they ask without a frame and never tiled or focused.
+    pub unmanaged_classes: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            workspaces: 9,
+            center_new_floating: true,
+            offscreen_margin: 50,
+            placement: Placement::Center,
+            spawn_at_pointer: false,
+            outer_border_width: 3,
+            outer_border_color: Color::Rgb(0xFF00FF),
+            urgent_border_color: Color::Rgb(0xFF0000),
+            stacked_border_color: Color::Rgb(0x00FFFF),
+            inner_border_width: 0,
+            inner_border_color: Color::Rgb(0x000000),
+            frame_background_color: Color::Rgb(0x0000FF),
+            borders_on_focused_monitor_only: false,
+            focused_monitor_border_width: 0,
+            focused_monitor_border_color: Color::Rgb(0xFF00FF),
+            keyboard_move_step: 20,
+            keyboard_move_acceleration: 1.0,
+            gap: 0,
+            smart_gaps: false,
+            resize_mode: ResizeMode::Live,
+            autostart: Vec::new(),
+            raise_on_click: true,
+            raise_on_focus: true,
+            corner_radius: 0,
+            wallpaper: None,
+            master_ratio: 0.5,
+            split_direction: SplitDirection::Vertical,
+            swallow: false,
+            opacity_step: 0.1,
+            inactive_opacity: 1.0,
+            new_window_workspace: NewWindowWorkspace::Named(NamedWorkspace::Primary),
+            primary_monitor: None,
+            keys: Vec::new(),
+            buttons: vec![
+                ButtonBinding {
+                    button: Button {
+                        modifiers: Mod1Mask,
+                        button: Button1,
+                    },
+                    action: ButtonAction::Move,
+                },
+                ButtonBinding {
+                    button: Button {
+                        modifiers: Mod1Mask,
+                        button: Button3,
+                    },
+                    action: ButtonAction::Resize,
+                },
+            ],
+            drag_threshold: 4,
+            snap_distance: 0,
+            rotate_focus: RotateFocus::Window,
+            window_switcher: false,
+            window_menu: "dmenu -i -l 20".to_string(),
+            focus_new_windows: false,
+            compositor_mode: false,
+            no_focus_classes: Vec::new(),
+            unmanaged_classes: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads `$XDG_CONFIG_HOME/wm-rs/config.toml` (or `~/.config/wm-rs/config.toml`), falling
+    /// back to the defaults if it is missing or invalid. Colors the file doesn't set are taken from
+    /// `resource_color`, given the X resource name of the color, before the defaults.
+    pub fn load(resource_color: impl Fn(&str) -> Option<String>) -> Config {
+        let (mut config, table) = Self::read().unwrap_or_default();
+        config.apply_resource_colors(&table, resource_color);
+        config
+    }
+
+    /// Like `load`, but None if the config file exists and can't be read or parsed, so a broken
+    /// edit doesn't throw away the config in use
+    pub fn reload(resource_color: impl Fn(&str) -> Option<String>) -> Option<Config> {
+        let (mut config, table) = Self::read()?;
+        config.apply_resource_colors(&table, resource_color);
+        Some(config)
+    }
+
+    /// The config and the keys it was given in the file, the defaults without a file, or None if
+    /// the file is broken
+    fn read() -> Option<(Config, toml::Table)> {
+        let defaults = || Some((Config::default(), toml::Table::new()));
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return defaults(),
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                info!("No config file at {:?}, using defaults", path);
+                return defaults();
+            }
+            Err(err) => {
+                error!("Failed to read config file {:?}: {}", path, err);
+                return None;
+            }
+        };
+
+        let mut config: Config = match toml::from_str(&contents) {
+            Ok(config) => config,
+            Err(err) => {
+                error!("Failed to parse config file {:?}: {}", path, err);
+                return None;
+            }
+        };
+        config.validate();
+        info!("Loaded config from {:?}", path);
+
+        Some((config, contents.parse().unwrap_or_default()))
+    }
+
+    fn apply_resource_colors(
+        &mut self,
+        table: &toml::Table,
+        resource_color: impl Fn(&str) -> Option<String>,
+    ) {
+        // The first of the resource names that is set is used. The outer border is what focused
+        // windows are drawn with, so it also goes by focusedBorderColor.
+        let colors: [(&str, &[&str], &mut Color); 6] = [
+            (
+                "outer_border_color",
+                &["borderColor", "focusedBorderColor"],
+                &mut self.outer_border_color,
+            ),
+            (
+                "urgent_border_color",
+                &["urgentBorderColor"],
+                &mut self.urgent_border_color,
+            ),
+            (
+                "stacked_border_color",
+                &["stackedBorderColor"],
+                &mut self.stacked_border_color,
+            ),
+            (
+                "inner_border_color",
+                &["innerBorderColor"],
+                &mut self.inner_border_color,
+            ),
+            (
+                "frame_background_color",
+                &["backgroundColor"],
+                &mut self.frame_background_color,
+            ),
+            (
+                "focused_monitor_border_color",
+                &["focusedMonitorBorderColor"],
+                &mut self.focused_monitor_border_color,
+            ),
+        ];
+        for (key, resources, field) in colors {
+            if table.contains_key(key) {
+                continue;
+            }
+            let found = resources
+                .iter()
+                .find_map(|&resource| Some((resource, resource_color(resource)?)));
+            if let Some((resource, color)) = found {
+                info!("Using {:?} from the X resource wm-rs.{}", color, resource);
+                *field = Color::Named(color);
+            }
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        Some(Self::dir()?.join("config.toml"))
+    }
+
+    /// `autostart.sh` next to the config file, which is run at startup if it exists
+    pub fn autostart_script() -> Option<PathBuf> {
+        Some(Self::dir()?.join("autostart.sh"))
+    }
+
+    fn dir() -> Option<PathBuf> {
+        let config_home = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+
+        Some(config_home.join("wm-rs"))
+    }
+
+    pub fn validate(&mut self) {
+        if self.workspaces < 1 {
+            warn!(
+                "There must be at least 1 workspace, got {}",
+                self.workspaces
+            );
+            self.workspaces = 1;
+        }
+        if let NewWindowWorkspace::Index(workspace) = self.new_window_workspace {
+            if workspace >= self.workspaces {
+                warn!(
+                    "New windows can't open on workspace {}, there are only {}",
+                    workspace, self.workspaces
+                );
+                self.new_window_workspace = NewWindowWorkspace::Named(NamedWorkspace::Current);
+            }
+        }
+        if !(0.1..=0.9).contains(&self.master_ratio) {
+            warn!(
+                "The master ratio must be between 0.1 and 0.9, got {}",
+                self.master_ratio
+            );
+            self.master_ratio = self.master_ratio.clamp(0.1, 0.9);
+        }
+        if self.keyboard_move_acceleration.is_nan() || self.keyboard_move_acceleration < 1.0 {
+            warn!(
+                "The keyboard move acceleration must be at least 1, got {}",
+                self.keyboard_move_acceleration
+            );
+            self.keyboard_move_acceleration = 1.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_window_workspace_is_named_or_an_index() {
+        let config: Config = toml::from_str("new_window_workspace = \"current\"").unwrap();
+        assert_eq!(
+            config.new_window_workspace,
+            NewWindowWorkspace::Named(NamedWorkspace::Current)
+        );
+        let config: Config = toml::from_str("new_window_workspace = \"primary\"").unwrap();
+        assert_eq!(
+            config.new_window_workspace,
+            NewWindowWorkspace::Named(NamedWorkspace::Primary)
+        );
+
+        let config: Config = toml::from_str("new_window_workspace = 2").unwrap();
+        assert_eq!(config.new_window_workspace, NewWindowWorkspace::Index(2));
+
+        assert!(toml::from_str::<Config>("new_window_workspace = \"elsewhere\"").is_err());
+    }
+
+    #[test]
+    fn key_bindings_are_parsed_by_name() {
+        let config: Config = toml::from_str(
+            r#"
+            [[keys]]
+            key = "Mod4+Return"
+            command = "alacritty"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.keys.len(), 1);
+        assert_eq!(config.keys[0].command, "alacritty");
+
+        let err = toml::from_str::<Config>(
+            r#"
+            [[keys]]
+            key = "Mod4+Retrun"
+            command = "alacritty"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Unknown key name \"Retrun\""));
+    }
+
+    #[test]
+    fn key_bindings_can_be_limited_to_a_class() {
+        let config: Config = toml::from_str(
+            r#"
+            [[keys]]
+            key = "XF86AudioPlay"
+            command = "playerctl play-pause"
+
+            [[keys]]
+            key = "XF86AudioPlay"
+            command = "echo cycle pause | socat - /tmp/mpv"
+            when_class = "mpv"
+            "#,
+        )
+        .unwrap();
+
+        let mpv = ("gl".to_string(), "mpv".to_string());
+        let firefox = ("Navigator".to_string(), "firefox".to_string());
+        assert!(config.keys[0].applies_to(None));
+        assert!(config.keys[1].applies_to(Some(&mpv)));
+        assert!(!config.keys[1].applies_to(Some(&firefox)));
+        assert!(!config.keys[1].applies_to(None));
+    }
+
+    #[test]
+    fn button_bindings_replace_the_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+            [[buttons]]
+            button = "Mod1+Button2"
+            action = "close"
+
+            [[buttons]]
+            button = "Mod4+Button1"
+            action = { command = "notify-send hi" }
+            "#,
+        )
+        .unwrap();
+
+        let actions: Vec<_> = config.buttons.iter().map(|b| b.action.clone()).collect();
+        assert_eq!(
+            actions,
+            [
+                ButtonAction::Close,
+                ButtonAction::Command("notify-send hi".to_string())
+            ]
+        );
+        assert_eq!(Config::default().buttons.len(), 2);
+    }
+
+    #[test]
+    fn colors_are_numbers_or_names() {
+        let config: Config = toml::from_str(
+            r##"
+            outer_border_color = 0xFF8800
+            urgent_border_color = "#FF0000"
+            inner_border_color = "slate gray"
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(config.outer_border_color, Color::Rgb(0xFF8800));
+        assert_eq!(
+            config.urgent_border_color,
+            Color::Named("#FF0000".to_string())
+        );
+        assert_eq!(
+            config.inner_border_color,
+            Color::Named("slate gray".to_string())
+        );
+    }
+
+    #[test]
+    fn resource_colors_only_fill_in_unset_colors() {
+        let table: toml::Table = "outer_border_color = 0x00FF00".parse().unwrap();
+        let mut config: Config = toml::from_str("outer_border_color = 0x00FF00").unwrap();
+        config.apply_resource_colors(&table, |name| {
+            (name != "innerBorderColor").then(|| "#123456".to_string())
+        });
+
+        assert_eq!(config.outer_border_color, Color::Rgb(0x00FF00));
+        assert_eq!(
+            config.urgent_border_color,
+            Color::Named("#123456".to_string())
+        );
+        assert_eq!(
+            config.inner_border_color,
+            Config::default().inner_border_color
+        );
+        assert_eq!(
+            config.frame_background_color,
+            Color::Named("#123456".to_string())
+        );
+
+        // focusedBorderColor stands in for borderColor
+        let mut config = Config::default();
+        config.apply_resource_colors(&toml::Table::new(), |name| {
+            (name == "focusedBorderColor").then(|| "navy".to_string())
+        });
+        assert_eq!(config.outer_border_color, Color::Named("navy".to_string()));
+        let mut config = Config::default();
+        config.apply_resource_colors(&toml::Table::new(), |name| match name {
+            "borderColor" => Some("red".to_string()),
+            "focusedBorderColor" => Some("navy".to_string()),
+            _ => None,
+        });
+        assert_eq!(config.outer_border_color, Color::Named("red".to_string()));
+    }
+
+    #[test]
+    fn out_of_range_workspaces_fall_back_to_the_current_one() {
+        let mut config: Config = toml::from_str("new_window_workspace = 9").unwrap();
+        config.validate();
+
+        assert_eq!(
+            config.new_window_workspace,
+            NewWindowWorkspace::Named(NamedWorkspace::Current)
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::backend::XBackend;
+use crate::client::ClientList;
+use x11::xlib::Window;
+
+/// Focuses, and if `raise` is set raises, the client `delta` steps away from `from` among the
+/// clients visible on `workspace` that may take the focus, wrapping around the list. Without a
+/// focused client the cycle starts at either end of the list. Returns the newly focused client, if
+/// there is any client to focus.
+pub fn focus_relative(
+    backend: &impl XBackend,
+    clients: &ClientList,
+    workspace: usize,
+    from: Option<Window>,
+    delta: isize,
+    raise: bool,
+) -> Option<Window> {
+    let candidates: Vec<_> = clients.visible(workspace).filter(|c| !c.no_focus).collect();
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let current = from.and_then(|w| candidates.iter().position(|c| c.window == w));
+    let next = candidates[relative_index(candidates.len(), current, delta)];
+
+    if raise {
+        backend.raise(next.frame);
+    }
+    backend.focus(next.window);
+    Some(next.window)
+}
+
+/// The index `delta` steps away from `current` in a list of `len` items, wrapping around. Without a
+/// current item the steps start from either end of the list.
+pub fn relative_index(len: usize, current: Option<usize>, delta: isize) -> usize {
+    let current = match current {
+        Some(i) => i as isize,
+        None if delta > 0 => -1,
+        None => 0,
+    };
+    (current + delta).rem_euclid(len as isize) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::{Call, MockBackend};
+
+    #[test]
+    fn focuses_the_next_client() {
+        let backend = MockBackend::new();
+        let clients = ClientList::from_windows(&[(1, 2), (3, 4), (5, 6)]);
+
+        assert_eq!(
+            focus_relative(&backend, &clients, 0, Some(3), 1, true),
+            Some(5)
+        );
+        assert_eq!(backend.calls(), vec![Call::Raise(6), Call::Focus(5)]);
+    }
+
+    #[test]
+    fn focuses_the_previous_client() {
+        let backend = MockBackend::new();
+        let clients = ClientList::from_windows(&[(1, 2), (3, 4), (5, 6)]);
+
+        assert_eq!(
+            focus_relative(&backend, &clients, 0, Some(3), -1, true),
+            Some(1)
+        );
+        assert_eq!(backend.calls(), vec![Call::Raise(2), Call::Focus(1)]);
+    }
+
+    #[test]
+    fn wraps_around() {
+        let clients = ClientList::from_windows(&[(1, 2), (3, 4)]);
+
+        assert_eq!(
+            focus_relative(&MockBackend::new(), &clients, 0, Some(3), 1, true),
+            Some(1)
+        );
+        assert_eq!(
+            focus_relative(&MockBackend::new(), &clients, 0, Some(1), -1, true),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn skips_hidden_clients() {
+        let backend = MockBackend::new();
+        let mut clients = ClientList::from_windows(&[(1, 2), (3, 4), (5, 6)]);
+        clients.client_mut(&3).unwrap().hidden = true;
+
+        assert_eq!(
+            focus_relative(&backend, &clients, 0, Some(1), 1, true),
+            Some(5)
+        );
+        assert_eq!(backend.calls(), vec![Call::Raise(6), Call::Focus(5)]);
+    }
+
+    #[test]
+    fn skips_no_focus_clients() {
+        let backend = MockBackend::new();
+        let mut clients = ClientList::from_windows(&[(1, 2), (3, 4), (5, 6)]);
+        clients.client_mut(&3).unwrap().no_focus = true;
+
+        assert_eq!(
+            focus_relative(&backend, &clients, 0, Some(1), 1, true),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn focuses_without_raising() {
+        let backend = MockBackend::new();
+        let clients = ClientList::from_windows(&[(1, 2), (3, 4)]);
+
+        assert_eq!(
+            focus_relative(&backend, &clients, 0, Some(1), 1, false),
+            Some(3)
+        );
+        assert_eq!(backend.calls(), vec![Call::Focus(3)]);
+    }
+
+    #[test]
+    fn stays_on_the_workspace() {
+        let backend = MockBackend::new();
+        let mut clients = ClientList::from_windows(&[(1, 2), (3, 4), (5, 6), (7, 8)]);
+        clients.client_mut(&3).unwrap().workspace = 1;
+        clients.client_mut(&5).unwrap().workspace = 1;
+        clients.client_mut(&5).unwrap().sticky = true;
+
+        assert_eq!(
+            focus_relative(&backend, &clients, 0, Some(1), 1, true),
+            Some(5)
+        );
+        assert_eq!(
+            focus_relative(&backend, &clients, 0, Some(5), 1, true),
+            Some(7)
+        );
+        assert_eq!(
+            focus_relative(&backend, &clients, 1, Some(5), 1, true),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn nothing_to_focus_on_an_empty_workspace() {
+        let backend = MockBackend::new();
+        let clients = ClientList::from_windows(&[(1, 2)]);
+
+        assert_eq!(
+            focus_relative(&backend, &clients, 1, Some(1), 1, true),
+            None
+        );
+        assert!(backend.calls().is_empty());
+    }
+
+    #[test]
+    fn starts_at_the_ends_without_focus() {
+        let clients = ClientList::from_windows(&[(1, 2), (3, 4), (5, 6)]);
+
+        assert_eq!(
+            focus_relative(&MockBackend::new(), &clients, 0, None, 1, true),
+            Some(1)
+        );
+        assert_eq!(
+            focus_relative(&MockBackend::new(), &clients, 0, None, -1, true),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn nothing_to_focus_in_an_empty_list() {
+        let backend = MockBackend::new();
+        let clients = ClientList::new();
+
+        assert_eq!(
+            focus_relative(&backend, &clients, 0, Some(1), 1, true),
+            None
+        );
+        assert_eq!(focus_relative(&backend, &clients, 0, None, -1, true), None);
+        assert!(backend.calls().is_empty());
+    }
+
+    #[test]
+    fn single_client_keeps_the_focus() {
+        let backend = MockBackend::new();
+        let clients = ClientList::from_windows(&[(1, 2)]);
+
+        assert_eq!(
+            focus_relative(&backend, &clients, 0, Some(1), 1, true),
+            Some(1)
+        );
+        assert_eq!(
+            focus_relative(&backend, &clients, 0, None, -1, true),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn unknown_window_starts_a_new_cycle() {
+        let clients = ClientList::from_windows(&[(1, 2), (3, 4)]);
+
+        assert_eq!(
+            focus_relative(&MockBackend::new(), &clients, 0, Some(42), 1, true),
+            Some(1)
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use std::ptr::NonNull;
+use x11::xlib::{Display, XFlush, XGrabServer, XUngrabServer};
+
+/// Holds the server grabbed until it is dropped, so the server is released on every path out of
+/// the grabbed section, panics included
+pub struct ServerGrab {
+    display: NonNull<Display>,
+}
+
+impl ServerGrab {
+    pub fn new(display: NonNull<Display>) -> Self {
+        unsafe {
+            XGrabServer(display.as_ptr());
+        }
+        ServerGrab { display }
+    }
+}
+
+impl Drop for ServerGrab {
+    fn drop(&mut self) {
+        unsafe {
+            XUngrabServer(self.display.as_ptr());
+            // Make sure the ungrab reaches the server even if we are about to go down
+            XFlush(self.display.as_ptr());
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::layout::Geometry;
+use crate::shape;
+use std::mem::MaybeUninit;
+use x11::xlib::{
+    CWBackPixel, CWOverrideRedirect, CopyFromParent, Display, Window, XClearWindow, XCreateWindow,
+    XMapRaised, XMoveResizeWindow, XRaiseWindow, XSetWindowAttributes, XSetWindowBackground,
+    XUnmapWindow,
+};
+
+/// Strips along the edges of the focused monitor, telling which one is focused when its windows
+/// have no borders
+pub struct MonitorHighlight {
+    /// The top, bottom, left and right strip
+    windows: [Window; 4],
+    shown: bool,
+}
+
+impl MonitorHighlight {
+    /// Creates the strips in `pixel`, hidden until they are shown on a monitor
+    pub fn new(display: *mut Display, root: Window, pixel: u64) -> Self {
+        let windows = [(); 4].map(|_| unsafe {
+            let mut attributes: XSetWindowAttributes = MaybeUninit::zeroed().assume_init();
+            // The window manager must not try to frame its own strips
+            attributes.override_redirect = 1;
+            attributes.background_pixel = pixel;
+            let window = XCreateWindow(
+                display,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                CopyFromParent,
+                CopyFromParent as u32,
+                std::ptr::null_mut(),
+                CWOverrideRedirect | CWBackPixel,
+                &mut attributes,
+            );
+            // Clicks go through to the windows at the edges of the monitor
+            shape::clear_input(display, window);
+            window
+        });
+        MonitorHighlight {
+            windows,
+            shown: false,
+        }
+    }
+
+    pub fn contains(&self, w: Window) -> bool {
+        self.windows.contains(&w)
+    }
+
+    /// Puts the strips `width` pixels wide along the inside of the edges of `area`
+    pub fn show(&mut self, display: *mut Display, area: Geometry, width: u32) {
+        for (w, strip) in self.windows.iter().zip(strips(area, width)) {
+            unsafe {
+                XMoveResizeWindow(display, *w, strip.x, strip.y, strip.width, strip.height);
+                XMapRaised(display, *w);
+            }
+        }
+        self.shown = true;
+    }
+
+    pub fn hide(&mut self, display: *mut Display) {
+        for w in self.windows {
+            unsafe { XUnmapWindow(display, w) };
+        }
+        self.shown = false;
+    }
+
+    /// Puts the strips back on top of a window that was just raised over them
+    pub fn raise(&self, display: *mut Display) {
+        if !self.shown {
+            return;
+        }
+        for w in self.windows {
+            unsafe { XRaiseWindow(display, w) };
+        }
+    }
+
+    pub fn set_color(&self, display: *mut Display, pixel: u64) {
+        for w in self.windows {
+            unsafe {
+                XSetWindowBackground(display, w, pixel);
+                XClearWindow(display, w);
+            }
+        }
+    }
+}
+
+/// The top, bottom, left and right strip of `width` inside `area`, the side ones fitting between
+/// the other two
+fn strips(area: Geometry, width: u32) -> [Geometry; 4] {
+    let width = width.min(area.width / 2).min(area.height / 2).max(1);
+    let side_height = (area.height - 2 * width).max(1);
+    let top = area.y + width as i32;
+    [
+        Geometry {
+            x: area.x,
+            y: area.y,
+            width: area.width,
+            height: width,
+        },
+        Geometry {
+            x: area.x,
+            y: area.y + (area.height - width) as i32,
+            width: area.width,
+            height: width,
+        },
+        Geometry {
+            x: area.x,
+            y: top,
+            width,
+            height: side_height,
+        },
+        Geometry {
+            x: area.x + (area.width - width) as i32,
+            y: top,
+            width,
+            height: side_height,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_line_the_monitor_edges() {
+        let area = Geometry {
+            x: 1920,
+            y: 0,
+            width: 1280,
+            height: 1024,
+        };
+        let strips: Vec<(i32, i32, u32, u32)> = strips(area, 2)
+            .iter()
+            .map(|g| (g.x, g.y, g.width, g.height))
+            .collect();
+        assert_eq!(
+            strips,
+            [
+                (1920, 0, 1280, 2),
+                (1920, 1022, 1280, 2),
+                (1920, 2, 2, 1020),
+                (3198, 2, 2, 1020),
+            ]
+        );
+    }
+}
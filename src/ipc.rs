@@ -0,0 +1,109 @@
+use std::env;
+use std::path::PathBuf;
+use x11::xlib::Window;
+
+/// A command read from the control socket, one per line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Switch to the workspace at this index
+    Workspace(usize),
+    /// Focus a window, switching to its workspace if needed
+    Focus(Window),
+    /// Close the focused window
+    Close,
+    Iconify,
+    Zoom,
+    Retile,
+    ResetLayout,
+    ToggleLayoutLock,
+    /// Read the config file again
+    Reload,
+    /// List the monitors, marking the primary and the focused one
+    Monitors,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().ok_or("empty command")?;
+        let argument = words.next();
+        if words.next().is_some() {
+            return Err(format!("too many arguments to {}", command));
+        }
+
+        let command = match (command, argument) {
+            ("workspace", Some(index)) => Command::Workspace(
+                index
+                    .parse()
+                    .map_err(|_| format!("invalid workspace {:?}", index))?,
+            ),
+            ("focus", Some(id)) => Command::Focus(
+                parse_window_id(id).ok_or_else(|| format!("invalid window id {:?}", id))?,
+            ),
+            ("close", None) => Command::Close,
+            ("iconify", None) => Command::Iconify,
+            ("zoom", None) => Command::Zoom,
+            ("retile", None) => Command::Retile,
+            ("reload", None) => Command::Reload,
+            ("monitors", None) => Command::Monitors,
+            ("layout", Some("reset")) => Command::ResetLayout,
+            ("layout", Some("lock")) => Command::ToggleLayoutLock,
+            ("layout", Some(layout)) => return Err(format!("unknown layout {:?}", layout)),
+            ("workspace" | "focus" | "layout", None) => {
+                return Err(format!("{} needs an argument", command))
+            }
+            ("close" | "iconify" | "zoom" | "retile" | "reload" | "monitors", Some(_)) => {
+                return Err(format!("{} takes no argument", command))
+            }
+            _ => return Err(format!("unknown command {:?}", command)),
+        };
+        Ok(command)
+    }
+}
+
+/// Window ids are accepted in hex with a `0x` prefix, as xprop and xwininfo print them, or in
+/// decimal
+fn parse_window_id(id: &str) -> Option<Window> {
+    match id.strip_prefix("0x") {
+        Some(hex) => Window::from_str_radix(hex, 16).ok(),
+        None => id.parse().ok(),
+    }
+}
+
+/// `wm-rs<display>.sock` in `$XDG_RUNTIME_DIR`, or in the temporary directory without one
+pub fn socket_path(display_name: &str) -> PathBuf {
+    let dir = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir);
+    dir.join(format!("wm-rs{}.sock", display_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands_with_arguments() {
+        assert_eq!(Command::parse("workspace 3"), Ok(Command::Workspace(3)));
+        assert_eq!(
+            Command::parse("focus 0x1a00003"),
+            Ok(Command::Focus(0x1a00003))
+        );
+        assert_eq!(Command::parse("focus 42"), Ok(Command::Focus(42)));
+        assert_eq!(Command::parse("  close \n"), Ok(Command::Close));
+        assert_eq!(Command::parse("layout reset"), Ok(Command::ResetLayout));
+        assert_eq!(Command::parse("reload"), Ok(Command::Reload));
+        assert_eq!(Command::parse("monitors"), Ok(Command::Monitors));
+    }
+
+    #[test]
+    fn rejects_malformed_commands() {
+        assert!(Command::parse("").is_err());
+        assert!(Command::parse("workspace").is_err());
+        assert!(Command::parse("workspace three").is_err());
+        assert!(Command::parse("focus 0xzz").is_err());
+        assert!(Command::parse("close now").is_err());
+        assert!(Command::parse("layout monocle").is_err());
+        assert!(Command::parse("dance").is_err());
+    }
+}
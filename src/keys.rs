@@ -0,0 +1,152 @@
+use serde::Deserialize;
+use std::ffi::CString;
+use std::os::raw::c_uint;
+use x11::xlib::{
+    Button1, Button2, Button3, Button4, Button5, ControlMask, KeySym, Mod1Mask, Mod2Mask, Mod3Mask,
+    Mod4Mask, Mod5Mask, ShiftMask, XConvertCase, XStringToKeysym,
+};
+
+/// A key combination written as modifier and keysym names joined by `+`, like `Mod1+Shift+Return`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Key {
+    pub modifiers: c_uint,
+    pub keysym: KeySym,
+}
+
+impl TryFrom<String> for Key {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        let (modifiers, keysym) = match name.rsplit_once('+') {
+            Some((modifiers, keysym)) => (parse_modifiers(modifiers)?, keysym),
+            None => (0, name.as_str()),
+        };
+
+        Ok(Key {
+            modifiers,
+            keysym: parse_keysym(keysym)?,
+        })
+    }
+}
+
+/// A mouse button with modifiers, written like a key but ending in `Button1` to `Button5`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Button {
+    pub modifiers: c_uint,
+    pub button: c_uint,
+}
+
+impl TryFrom<String> for Button {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        let (modifiers, button) = match name.rsplit_once('+') {
+            Some((modifiers, button)) => (parse_modifiers(modifiers)?, button),
+            None => (0, name.as_str()),
+        };
+        let button = match button {
+            "Button1" => Button1,
+            "Button2" => Button2,
+            "Button3" => Button3,
+            "Button4" => Button4,
+            "Button5" => Button5,
+            _ => return Err(format!("Unknown button name {:?}", button)),
+        };
+
+        Ok(Button { modifiers, button })
+    }
+}
+
+fn parse_modifiers(names: &str) -> Result<c_uint, String> {
+    names
+        .split('+')
+        .map(parse_modifier)
+        .try_fold(0, |modifiers, modifier| Ok(modifiers | modifier?))
+}
+
+fn parse_keysym(name: &str) -> Result<KeySym, String> {
+    let keysym = CString::new(name)
+        .map(|name| unsafe { XStringToKeysym(name.as_ptr()) })
+        .unwrap_or(0);
+    if keysym == 0 {
+        return Err(format!("Unknown key name {:?}", name));
+    }
+    Ok(normalize(keysym))
+}
+
+fn parse_modifier(name: &str) -> Result<c_uint, String> {
+    match name {
+        "Shift" => Ok(ShiftMask),
+        "Control" | "Ctrl" => Ok(ControlMask),
+        "Mod1" | "Alt" => Ok(Mod1Mask),
+        "Mod2" => Ok(Mod2Mask),
+        "Mod3" => Ok(Mod3Mask),
+        "Mod4" | "Super" => Ok(Mod4Mask),
+        "Mod5" => Ok(Mod5Mask),
+        _ => Err(format!("Unknown modifier {:?}", name)),
+    }
+}
+
+/// The upper case version of `keysym`, so `q` and `Q` name the same binding. Shift is matched
+/// separately as a modifier.
+pub fn normalize(keysym: KeySym) -> KeySym {
+    let mut lower = 0;
+    let mut upper = 0;
+    unsafe {
+        XConvertCase(keysym, &mut lower, &mut upper);
+    }
+    upper
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11::keysym::{XK_Return, XK_Q};
+
+    fn parse(name: &str) -> Result<Key, String> {
+        Key::try_from(name.to_string())
+    }
+
+    #[test]
+    fn parses_modifiers_and_keysym() {
+        assert_eq!(
+            parse("Mod4+Shift+Return"),
+            Ok(Key {
+                modifiers: Mod4Mask | ShiftMask,
+                keysym: XK_Return.into(),
+            })
+        );
+    }
+
+    #[test]
+    fn letters_are_case_insensitive() {
+        let key = Key {
+            modifiers: Mod1Mask,
+            keysym: XK_Q.into(),
+        };
+        assert_eq!(parse("Mod1+q"), Ok(key));
+        assert_eq!(parse("Alt+Q"), Ok(key));
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        assert!(parse("Mod1+Retrun").is_err());
+        assert!(parse("Hyper+q").is_err());
+        assert!(parse("Mod1+").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn parses_buttons() {
+        assert_eq!(
+            Button::try_from("Mod1+Shift+Button2".to_string()),
+            Ok(Button {
+                modifiers: Mod1Mask | ShiftMask,
+                button: Button2,
+            })
+        );
+        assert!(Button::try_from("Mod1+Button9".to_string()).is_err());
+    }
+}
@@ -0,0 +1,1229 @@
+use crate::backend::XBackend;
+use crate::config::SplitDirection;
+use std::ops::Range;
+use x11::xlib::{
+    CWBorderWidth, CWHeight, CWSibling, CWStackMode, CWWidth, Window, XWindowChanges, CWX, CWY,
+};
+
+/// How many times its configured size a keyboard move or resize step can grow to
+const MAX_ACCELERATION: f32 = 10.0;
+
+/// The outer rectangle of a window, including its border
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Geometry {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && x < self.x + self.width as i32
+            && y < self.y + self.height as i32
+    }
+
+    /// Area of the intersection with `other`
+    pub fn overlap(&self, other: &Geometry) -> u64 {
+        let width =
+            (self.x + self.width as i32).min(other.x + other.width as i32) - self.x.max(other.x);
+        let height =
+            (self.y + self.height as i32).min(other.y + other.height as i32) - self.y.max(other.y);
+        width.max(0) as u64 * height.max(0) as u64
+    }
+}
+
+/// The width or height to give a window so that, with its border drawn outside of that size as X
+/// does, it takes up `outer` pixels
+pub fn inner_size(outer: u32, border_width: u32) -> u32 {
+    outer.saturating_sub(2 * border_width)
+}
+
+/// Moves a `width`x`height` rectangle at (`x`, `y`) so it lies within the area as far as possible,
+/// preferring to keep its top-left corner visible if it is larger than the area
+pub fn clamp_to_area(
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    area_width: u32,
+    area_height: u32,
+) -> (i32, i32) {
+    (
+        x.min(area_width as i32 - width as i32).max(0),
+        y.min(area_height as i32 - height as i32).max(0),
+    )
+}
+
+/// Where to put a frame so the client inside it stays at (`x`, `y`), the position it asked for
+pub fn frame_position(x: i32, y: i32, border_width: u32, inner_border_width: u32) -> (i32, i32) {
+    let offset = (border_width + inner_border_width) as i32;
+    (x - offset, y - offset)
+}
+
+/// Where to put the frame of a new `width`x`height` client asking to be at (`x`, `y`), extended by
+/// the borders and moved back so that at least `margin` pixels of it stay inside the area
+#[allow(clippy::too_many_arguments)]
+pub fn requested_frame_position(
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    border_width: u32,
+    inner_border_width: u32,
+    (area_width, area_height): (u32, u32),
+    margin: u32,
+) -> (i32, i32) {
+    let (x, y) = frame_position(x, y, border_width, inner_border_width);
+    let borders = 2 * (border_width + inner_border_width);
+    keep_partially_visible(
+        x,
+        y,
+        width + borders,
+        height + borders,
+        area_width,
+        area_height,
+        margin,
+    )
+}
+
+/// Where the client inside a frame at (`x`, `y`) is on screen, the inverse of `frame_position`
+pub fn client_position(x: i32, y: i32, border_width: u32, inner_border_width: u32) -> (i32, i32) {
+    let offset = (border_width + inner_border_width) as i32;
+    (x + offset, y + offset)
+}
+
+/// Puts the client `w` into its `frame`, inset by the inner border drawn by the frame's background
+pub fn reparent_into_frame(
+    backend: &impl XBackend,
+    w: Window,
+    frame: Window,
+    inner_border_width: u32,
+) {
+    let inset = inner_border_width as i32;
+    backend.reparent(w, frame, inset, inset);
+}
+
+/// Puts the client `w` back on the `root` window where it was seen inside its frame at (`x`, `y`)
+pub fn reparent_out_of_frame(
+    backend: &impl XBackend,
+    w: Window,
+    root: Window,
+    (x, y): (i32, i32),
+    border_width: u32,
+    inner_border_width: u32,
+) {
+    let (x, y) = client_position(x, y, border_width, inner_border_width);
+    backend.reparent(w, root, x, y);
+}
+
+/// Applies a ConfigureRequest from the client `w` to it and its `frame`. The frame is what moves
+/// and gets stacked on screen, so it takes the requested position and grows with the requested
+/// size, while the client stays in place inside it and only changes its size and border.
+pub fn configure_framed(
+    backend: &impl XBackend,
+    w: Window,
+    frame: Window,
+    value_mask: u32,
+    changes: XWindowChanges,
+    border_width: u32,
+    inner_border_width: u32,
+) {
+    let (x, y) = frame_position(changes.x, changes.y, border_width, inner_border_width);
+    let frame_changes = XWindowChanges {
+        x,
+        y,
+        width: changes.width + 2 * inner_border_width as i32,
+        height: changes.height + 2 * inner_border_width as i32,
+        ..changes
+    };
+    let frame_mask = value_mask & (CWX | CWY | CWWidth | CWHeight | CWSibling | CWStackMode) as u32;
+    if frame_mask != 0 {
+        backend.configure(frame, frame_mask, frame_changes);
+    }
+
+    let client_mask = value_mask & (CWWidth | CWHeight | CWBorderWidth) as u32;
+    if client_mask != 0 {
+        backend.configure(w, client_mask, changes);
+    }
+}
+
+/// Where to put a `width`x`height` window `step` pixels down and right of the `last` cascaded one,
+/// starting over from the top left corner once it would leave the area
+pub fn cascade_position(
+    last: Option<(i32, i32)>,
+    width: u32,
+    height: u32,
+    area_width: u32,
+    area_height: u32,
+    step: i32,
+) -> (i32, i32) {
+    let (x, y) = match last {
+        Some((x, y)) => (x + step, y + step),
+        None => (0, 0),
+    };
+    if x + width as i32 > area_width as i32 || y + height as i32 > area_height as i32 {
+        return (0, 0);
+    }
+    (x, y)
+}
+
+/// The spot on a grid of `step` pixels where a `width`x`height` window overlaps the `existing`
+/// windows the least, preferring spots closer to the top left
+pub fn smart_position(
+    width: u32,
+    height: u32,
+    area_width: u32,
+    area_height: u32,
+    existing: &[Geometry],
+    step: u32,
+) -> (i32, i32) {
+    let max_x = area_width.saturating_sub(width);
+    let max_y = area_height.saturating_sub(height);
+    let step = step.max(1) as usize;
+
+    let mut best = ((0, 0), u64::MAX);
+    for y in (0..=max_y).step_by(step) {
+        for x in (0..=max_x).step_by(step) {
+            let candidate = Geometry {
+                x: x as i32,
+                y: y as i32,
+                width,
+                height,
+            };
+            let overlap = existing.iter().map(|g| candidate.overlap(g)).sum();
+            if overlap < best.1 {
+                best = ((x as i32, y as i32), overlap);
+            }
+            if overlap == 0 {
+                return best.0;
+            }
+        }
+    }
+    best.0
+}
+
+/// `step` grown by `acceleration` for each of the `repeats` presses before this one, up to
+/// `MAX_ACCELERATION` times its size
+pub fn accelerated_step(step: u32, acceleration: f32, repeats: u32) -> u32 {
+    let factor = acceleration
+        .powi(repeats.min(i32::MAX as u32) as i32)
+        .min(MAX_ACCELERATION);
+    (step as f32 * factor).round() as u32
+}
+
+/// Moves a `width`x`height` rectangle at (`x`, `y`) so that at least `visible` pixels of it remain
+/// inside the area in each direction
+pub fn keep_partially_visible(
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    area_width: u32,
+    area_height: u32,
+    visible: u32,
+) -> (i32, i32) {
+    let visible_x = visible.min(width) as i32;
+    let visible_y = visible.min(height) as i32;
+    (
+        x.max(visible_x - width as i32)
+            .min(area_width as i32 - visible_x),
+        y.max(visible_y - height as i32)
+            .min(area_height as i32 - visible_y),
+    )
+}
+
+/// The position to move `moving` to so that its edges line up with the edges of the area, or of
+/// any of `others` beside it, that are within `distance` pixels
+pub fn snap(
+    moving: Geometry,
+    others: &[Geometry],
+    area_width: u32,
+    area_height: u32,
+    distance: u32,
+) -> (i32, i32) {
+    let end = |start: i32, length: u32| start + length as i32;
+    // Only windows beside each other along one axis can line up along the other
+    let x_targets = others
+        .iter()
+        .filter(|o| o.y < end(moving.y, moving.height) && moving.y < end(o.y, o.height))
+        .flat_map(|o| [o.x, end(o.x, o.width)]);
+    let y_targets = others
+        .iter()
+        .filter(|o| o.x < end(moving.x, moving.width) && moving.x < end(o.x, o.width))
+        .flat_map(|o| [o.y, end(o.y, o.height)]);
+
+    let x_offset = snap_offset(
+        moving.x,
+        moving.width,
+        x_targets.chain([0, area_width as i32]),
+        distance,
+    );
+    let y_offset = snap_offset(
+        moving.y,
+        moving.height,
+        y_targets.chain([0, area_height as i32]),
+        distance,
+    );
+    (moving.x + x_offset, moving.y + y_offset)
+}
+
+/// The smallest shift that puts either end of the span at `start` on one of `targets`, or 0 if
+/// none is within `distance`
+fn snap_offset(start: i32, length: u32, targets: impl Iterator<Item = i32>, distance: u32) -> i32 {
+    let edges = [start, start + length as i32];
+    targets
+        .flat_map(|target| edges.map(|edge| target - edge))
+        .filter(|offset| offset.unsigned_abs() <= distance)
+        .min_by_key(|offset| offset.unsigned_abs())
+        .unwrap_or(0)
+}
+
+/// How the tiled windows of a workspace are arranged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// A master area next to a stack, see `tile`
+    Tile,
+    /// Every window fills the whole area, with the focused one on top
+    Monocle,
+}
+
+/// Borders and gaps that `tile` leaves around and between windows
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Spacing {
+    pub border_width: u32,
+    /// Inset of each client within its frame
+    pub inner_border_width: u32,
+    /// Space around the edge of the area and between windows
+    pub gap: u32,
+    /// Drop the gaps when there is only one window
+    pub smart_gaps: bool,
+}
+
+fn master_width(usable_width: u32, gap: u32, master_ratio: f32) -> u32 {
+    (usable_width.saturating_sub(gap) as f32 * master_ratio) as u32
+}
+
+/// The position of the gap between the master and stack areas along an area `length` pixels long
+/// in the split direction, when tiling more than one window, see `tile`
+pub fn divider_position(length: u32, spacing: Spacing, master_ratio: f32) -> i32 {
+    let usable_length = length.saturating_sub(2 * spacing.gap);
+    (spacing.gap + master_width(usable_length, spacing.gap, master_ratio) + spacing.gap / 2) as i32
+}
+
+/// The master ratio that puts the divider at `position`, the inverse of `divider_position`
+pub fn master_ratio_at(position: i32, length: u32, spacing: Spacing) -> f32 {
+    let gap = spacing.gap as i32;
+    let master_length = position - gap - gap / 2;
+    let usable_length = length.saturating_sub(3 * spacing.gap).max(1);
+    master_length as f32 / usable_length as f32
+}
+
+/// Tiles `windows`, given as (client, frame, decorated, size factor) tuples, over the `area` with
+/// the first `nmaster` windows in the master column on the left, taking `master_ratio` of the
+/// width, and the rest stacked on the right. Each column's height is shared in proportion to the
+/// size factors. Undecorated windows have no borders and fill their whole slot. A horizontal
+/// `direction` turns the columns into rows, with the master row at the top.
+#[allow(clippy::too_many_arguments)]
+pub fn tile(
+    backend: &impl XBackend,
+    windows: &[(Window, Window, bool, f32)],
+    area: Geometry,
+    spacing: Spacing,
+    master_ratio: f32,
+    nmaster: usize,
+    direction: SplitDirection,
+) {
+    // A horizontal split is laid out as a vertical one with the axes swapped
+    let horizontal = direction == SplitDirection::Horizontal;
+    let (width, height) = if horizontal {
+        (area.height, area.width)
+    } else {
+        (area.width, area.height)
+    };
+    let win_count = windows.len();
+    if win_count == 0 {
+        return;
+    }
+    let gap = if spacing.smart_gaps && win_count == 1 {
+        0
+    } else {
+        spacing.gap
+    };
+
+    let place = |(w, f, decorated, _): (Window, Window, bool, f32),
+                 x: u32,
+                 y: u32,
+                 width: u32,
+                 height: u32| {
+        let (x, y, width, height) = if horizontal {
+            (y, x, height, width)
+        } else {
+            (x, y, width, height)
+        };
+        let (border_width, inner_border_width) = if decorated {
+            (spacing.border_width, spacing.inner_border_width)
+        } else {
+            (0, 0)
+        };
+        let width = inner_size(width, border_width);
+        let height = inner_size(height, border_width);
+        backend.move_resize(f, area.x + x as i32, area.y + y as i32, width, height);
+        backend.move_resize(
+            w,
+            inner_border_width as i32,
+            inner_border_width as i32,
+            width.saturating_sub(2 * inner_border_width),
+            height.saturating_sub(2 * inner_border_width),
+        );
+    };
+
+    let usable_width = width.saturating_sub(2 * gap);
+    let usable_height = height.saturating_sub(2 * gap);
+
+    let column = |windows: &[(Window, Window, bool, f32)], x: u32, width: u32| {
+        if let [window] = windows {
+            place(*window, x, gap, width, usable_height);
+            return;
+        }
+        let column_height = usable_height.saturating_sub((windows.len() as u32 - 1) * gap);
+        let total_factor: f64 = windows.iter().map(|w| w.3 as f64).sum();
+        let mut y = gap;
+        for &window in windows {
+            let slot_height = (column_height as f64 * window.3 as f64 / total_factor) as u32;
+            place(window, x, y, width, slot_height);
+            y += slot_height + gap;
+        }
+    };
+
+    let (masters, stack) = windows.split_at(nmaster.clamp(1, win_count));
+    if stack.is_empty() {
+        column(masters, gap, usable_width);
+        return;
+    }
+
+    let master_width = master_width(usable_width, gap, master_ratio);
+    column(masters, gap, master_width);
+
+    let stack_x = gap + master_width + gap;
+    let stack_width = usable_width.saturating_sub(master_width + gap);
+    column(stack, stack_x, stack_width);
+}
+
+/// Gives each of `windows` the whole `area`, where it is tiled as if it were the only window, so
+/// whichever is raised covers the others
+pub fn monocle(
+    backend: &impl XBackend,
+    windows: &[(Window, Window, bool, f32)],
+    area: Geometry,
+    spacing: Spacing,
+) {
+    for &window in windows {
+        tile(
+            backend,
+            &[window],
+            area,
+            spacing,
+            0.5,
+            1,
+            SplitDirection::Vertical,
+        );
+    }
+}
+
+/// The indices of the windows in the same column as the one at `index` when `count` windows are
+/// tiled with `nmaster` of them in the master column
+pub fn area(count: usize, nmaster: usize, index: usize) -> Range<usize> {
+    let nmaster = nmaster.clamp(1, count.max(1));
+    if index < nmaster {
+        0..nmaster
+    } else {
+        nmaster..count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::{Call, MockBackend};
+
+    fn screen(width: u32, height: u32) -> Geometry {
+        Geometry {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    fn changes(x: i32, y: i32, width: i32, height: i32) -> XWindowChanges {
+        XWindowChanges {
+            x,
+            y,
+            width,
+            height,
+            border_width: 0,
+            sibling: 0,
+            stack_mode: 0,
+        }
+    }
+
+    #[test]
+    fn cascade_steps_down_and_wraps_around() {
+        assert_eq!(cascade_position(None, 100, 100, 400, 300, 30), (0, 0));
+        assert_eq!(
+            cascade_position(Some((30, 30)), 100, 100, 400, 300, 30),
+            (60, 60)
+        );
+        assert_eq!(
+            cascade_position(Some((180, 180)), 100, 100, 400, 300, 30),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn smart_placement_avoids_existing_windows() {
+        let existing = [Geometry {
+            x: 0,
+            y: 0,
+            width: 200,
+            height: 300,
+        }];
+
+        assert_eq!(smart_position(100, 100, 400, 300, &existing, 20), (200, 0));
+    }
+
+    #[test]
+    fn smart_placement_minimizes_overlap_when_full() {
+        let existing = [
+            Geometry {
+                x: 0,
+                y: 0,
+                width: 400,
+                height: 200,
+            },
+            Geometry {
+                x: 0,
+                y: 200,
+                width: 200,
+                height: 100,
+            },
+        ];
+
+        assert_eq!(
+            smart_position(200, 200, 400, 300, &existing, 20),
+            (200, 100)
+        );
+    }
+
+    #[test]
+    fn clients_moving_themselves_move_their_frame() {
+        let backend = MockBackend::new();
+        configure_framed(
+            &backend,
+            1,
+            2,
+            (CWX | CWY) as u32,
+            changes(100, 50, 0, 0),
+            3,
+            2,
+        );
+
+        // The client stays where it is inside the frame
+        assert_eq!(
+            backend.calls(),
+            vec![Call::Configure {
+                w: 2,
+                value_mask: (CWX | CWY) as u32,
+                changes: changes(95, 45, 4, 4),
+            }]
+        );
+    }
+
+    #[test]
+    fn resizing_clients_grow_their_frame() {
+        let backend = MockBackend::new();
+        configure_framed(
+            &backend,
+            1,
+            2,
+            (CWWidth | CWHeight) as u32,
+            changes(0, 0, 300, 200),
+            3,
+            2,
+        );
+
+        assert_eq!(
+            backend.calls(),
+            vec![
+                Call::Configure {
+                    w: 2,
+                    value_mask: (CWWidth | CWHeight) as u32,
+                    changes: changes(-5, -5, 304, 204),
+                },
+                Call::Configure {
+                    w: 1,
+                    value_mask: (CWWidth | CWHeight) as u32,
+                    changes: changes(0, 0, 300, 200),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn single_window_fills_the_area() {
+        let backend = MockBackend::new();
+        tile(
+            &backend,
+            &[(1, 2, true, 1.0)],
+            screen(800, 600),
+            Spacing {
+                border_width: 3,
+                ..Spacing::default()
+            },
+            0.5,
+            1,
+            SplitDirection::Vertical,
+        );
+
+        assert_eq!(
+            backend.calls(),
+            vec![
+                Call::MoveResize {
+                    w: 2,
+                    x: 0,
+                    y: 0,
+                    width: 794,
+                    height: 594,
+                },
+                Call::MoveResize {
+                    w: 1,
+                    x: 0,
+                    y: 0,
+                    width: 794,
+                    height: 594,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn borders_fit_inside_the_gaps() {
+        let backend = MockBackend::new();
+        let spacing = Spacing {
+            border_width: 3,
+            gap: 10,
+            ..Spacing::default()
+        };
+        tile(
+            &backend,
+            &[(1, 2, true, 1.0), (3, 4, true, 1.0), (5, 6, true, 1.0)],
+            screen(800, 600),
+            spacing,
+            0.5,
+            1,
+            SplitDirection::Vertical,
+        );
+
+        // Each frame keeps the slot's position and shrinks by its border on both sides, so the
+        // borders don't eat into the gaps
+        let slot = |w, x, y, width, height| Call::MoveResize {
+            w,
+            x,
+            y,
+            width,
+            height,
+        };
+        assert_eq!(
+            backend.calls(),
+            vec![
+                slot(2, 10, 10, 379, 574),
+                slot(1, 0, 0, 379, 574),
+                slot(4, 405, 10, 379, 279),
+                slot(3, 0, 0, 379, 279),
+                slot(6, 405, 305, 379, 279),
+                slot(5, 0, 0, 379, 279),
+            ]
+        );
+    }
+
+    #[test]
+    fn windows_are_tiled_within_their_monitor() {
+        let backend = MockBackend::new();
+        let monitor = Geometry {
+            x: 1920,
+            y: 56,
+            width: 1280,
+            height: 1024,
+        };
+        tile(
+            &backend,
+            &[(1, 2, true, 1.0), (3, 4, true, 1.0)],
+            monitor,
+            Spacing::default(),
+            0.5,
+            1,
+            SplitDirection::Vertical,
+        );
+
+        // Only the frames are placed on the root, the clients stay in their corner
+        let slot = |w, x, y, width, height| Call::MoveResize {
+            w,
+            x,
+            y,
+            width,
+            height,
+        };
+        assert_eq!(
+            backend.calls(),
+            vec![
+                slot(2, 1920, 56, 640, 1024),
+                slot(1, 0, 0, 640, 1024),
+                slot(4, 2560, 56, 640, 1024),
+                slot(3, 0, 0, 640, 1024),
+            ]
+        );
+    }
+
+    #[test]
+    fn monocle_gives_every_window_the_whole_area() {
+        let backend = MockBackend::new();
+        let spacing = Spacing {
+            border_width: 3,
+            gap: 10,
+            smart_gaps: true,
+            ..Spacing::default()
+        };
+        monocle(
+            &backend,
+            &[(1, 2, true, 1.0), (3, 4, false, 2.0)],
+            screen(800, 600),
+            spacing,
+        );
+
+        // Each window is alone in the area, so smart gaps leave it out
+        let slot = |w, x, y, width, height| Call::MoveResize {
+            w,
+            x,
+            y,
+            width,
+            height,
+        };
+        assert_eq!(
+            backend.calls(),
+            vec![
+                slot(2, 0, 0, 794, 594),
+                slot(1, 0, 0, 794, 594),
+                slot(4, 0, 0, 800, 600),
+                slot(3, 0, 0, 800, 600),
+            ]
+        );
+    }
+
+    #[test]
+    fn stack_is_split_evenly() {
+        let backend = MockBackend::new();
+        tile(
+            &backend,
+            &[(1, 2, true, 1.0), (3, 4, true, 1.0), (5, 6, true, 1.0)],
+            screen(800, 600),
+            Spacing {
+                border_width: 3,
+                ..Spacing::default()
+            },
+            0.5,
+            1,
+            SplitDirection::Vertical,
+        );
+
+        let frames: Vec<Call> = backend
+            .calls()
+            .into_iter()
+            .filter(|call| matches!(call, Call::MoveResize { w, .. } if w % 2 == 0))
+            .collect();
+        assert_eq!(
+            frames,
+            vec![
+                Call::MoveResize {
+                    w: 2,
+                    x: 0,
+                    y: 0,
+                    width: 394,
+                    height: 594,
+                },
+                Call::MoveResize {
+                    w: 4,
+                    x: 400,
+                    y: 0,
+                    width: 394,
+                    height: 294,
+                },
+                Call::MoveResize {
+                    w: 6,
+                    x: 400,
+                    y: 300,
+                    width: 394,
+                    height: 294,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn several_windows_share_the_master_column() {
+        let backend = MockBackend::new();
+        tile(
+            &backend,
+            &[(1, 2, true, 1.0), (3, 4, true, 1.0), (5, 6, true, 1.0)],
+            screen(800, 600),
+            Spacing::default(),
+            0.5,
+            2,
+            SplitDirection::Vertical,
+        );
+
+        let frames: Vec<(i32, i32, u32)> = backend
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                Call::MoveResize {
+                    w, x, y, height, ..
+                } if w % 2 == 0 => Some((x, y, height)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(frames, vec![(0, 0, 300), (0, 300, 300), (400, 0, 600)]);
+    }
+
+    #[test]
+    fn areas_split_at_nmaster() {
+        assert_eq!(area(5, 2, 1), 0..2);
+        assert_eq!(area(5, 2, 2), 2..5);
+        // More masters than windows leaves everything in the master column
+        assert_eq!(area(3, 9, 2), 0..3);
+        assert_eq!(area(3, 0, 0), 0..1);
+    }
+
+    #[test]
+    fn stack_is_split_by_size_factor() {
+        let backend = MockBackend::new();
+        tile(
+            &backend,
+            &[(1, 2, true, 1.0), (3, 4, true, 2.0), (5, 6, true, 1.0)],
+            screen(800, 600),
+            Spacing::default(),
+            0.5,
+            1,
+            SplitDirection::Vertical,
+        );
+
+        let stack: Vec<(i32, u32)> = backend
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                Call::MoveResize { w, y, height, .. } if w == 4 || w == 6 => Some((y, height)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(stack, vec![(0, 400), (400, 200)]);
+    }
+
+    #[test]
+    fn clients_are_inset_by_the_inner_border() {
+        let backend = MockBackend::new();
+        tile(
+            &backend,
+            &[(1, 2, true, 1.0)],
+            screen(800, 600),
+            Spacing {
+                border_width: 3,
+                inner_border_width: 2,
+                ..Spacing::default()
+            },
+            0.5,
+            1,
+            SplitDirection::Vertical,
+        );
+
+        assert_eq!(
+            backend.calls()[1],
+            Call::MoveResize {
+                w: 1,
+                x: 2,
+                y: 2,
+                width: 790,
+                height: 590,
+            }
+        );
+    }
+
+    #[test]
+    fn gaps_surround_and_separate_windows() {
+        let backend = MockBackend::new();
+        let spacing = Spacing {
+            gap: 10,
+            ..Spacing::default()
+        };
+        tile(
+            &backend,
+            &[(1, 2, true, 1.0), (3, 4, true, 1.0), (5, 6, true, 1.0)],
+            screen(800, 600),
+            spacing,
+            0.5,
+            1,
+            SplitDirection::Vertical,
+        );
+
+        let calls = backend.calls();
+        assert_eq!(
+            calls[0],
+            Call::MoveResize {
+                w: 2,
+                x: 10,
+                y: 10,
+                width: 385,
+                height: 580,
+            }
+        );
+        assert_eq!(
+            calls[2],
+            Call::MoveResize {
+                w: 4,
+                x: 405,
+                y: 10,
+                width: 385,
+                height: 285,
+            }
+        );
+        assert_eq!(
+            calls[4],
+            Call::MoveResize {
+                w: 6,
+                x: 405,
+                y: 305,
+                width: 385,
+                height: 285,
+            }
+        );
+    }
+
+    #[test]
+    fn smart_gaps_drop_gaps_for_a_single_window() {
+        let backend = MockBackend::new();
+        let spacing = Spacing {
+            gap: 10,
+            smart_gaps: true,
+            ..Spacing::default()
+        };
+        tile(
+            &backend,
+            &[(1, 2, true, 1.0)],
+            screen(800, 600),
+            spacing,
+            0.5,
+            1,
+            SplitDirection::Vertical,
+        );
+
+        assert_eq!(
+            backend.calls()[0],
+            Call::MoveResize {
+                w: 2,
+                x: 0,
+                y: 0,
+                width: 800,
+                height: 600,
+            }
+        );
+    }
+
+    #[test]
+    fn undecorated_windows_fill_their_slot() {
+        let backend = MockBackend::new();
+        let spacing = Spacing {
+            border_width: 3,
+            inner_border_width: 2,
+            ..Spacing::default()
+        };
+        tile(
+            &backend,
+            &[(1, 2, false, 1.0)],
+            screen(800, 600),
+            spacing,
+            0.5,
+            1,
+            SplitDirection::Vertical,
+        );
+
+        assert_eq!(
+            backend.calls(),
+            vec![
+                Call::MoveResize {
+                    w: 2,
+                    x: 0,
+                    y: 0,
+                    width: 800,
+                    height: 600,
+                },
+                Call::MoveResize {
+                    w: 1,
+                    x: 0,
+                    y: 0,
+                    width: 800,
+                    height: 600,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn master_ratio_sets_the_master_width() {
+        let backend = MockBackend::new();
+        tile(
+            &backend,
+            &[(1, 2, true, 1.0), (3, 4, true, 1.0)],
+            screen(800, 600),
+            Spacing::default(),
+            0.75,
+            1,
+            SplitDirection::Vertical,
+        );
+
+        let calls = backend.calls();
+        assert_eq!(
+            calls[0],
+            Call::MoveResize {
+                w: 2,
+                x: 0,
+                y: 0,
+                width: 600,
+                height: 600,
+            }
+        );
+        assert_eq!(
+            calls[2],
+            Call::MoveResize {
+                w: 4,
+                x: 600,
+                y: 0,
+                width: 200,
+                height: 600,
+            }
+        );
+        assert_eq!(divider_position(800, Spacing::default(), 0.75), 600);
+    }
+
+    #[test]
+    fn dragging_the_divider_follows_the_pointer_across_gaps() {
+        let spacing = Spacing {
+            gap: 10,
+            ..Spacing::default()
+        };
+        assert_eq!(divider_position(800, spacing, 0.5), 400);
+        assert_eq!(master_ratio_at(400, 800, spacing), 0.5);
+        // The master area ends half a gap before the pointer
+        assert_eq!(master_ratio_at(785, 800, spacing), 1.0);
+        assert_eq!(master_ratio_at(15, 800, spacing), 0.0);
+        assert_eq!(master_ratio_at(200, 800, Spacing::default()), 0.25);
+    }
+
+    #[test]
+    fn horizontal_split_puts_the_master_on_top() {
+        let backend = MockBackend::new();
+        tile(
+            &backend,
+            &[(1, 2, true, 1.0), (3, 4, true, 1.0), (5, 6, true, 1.0)],
+            screen(800, 600),
+            Spacing::default(),
+            0.5,
+            1,
+            SplitDirection::Horizontal,
+        );
+
+        let frames: Vec<Call> = backend
+            .calls()
+            .into_iter()
+            .filter(|call| matches!(call, Call::MoveResize { w, .. } if w % 2 == 0))
+            .collect();
+        assert_eq!(
+            frames,
+            vec![
+                Call::MoveResize {
+                    w: 2,
+                    x: 0,
+                    y: 0,
+                    width: 800,
+                    height: 300,
+                },
+                Call::MoveResize {
+                    w: 4,
+                    x: 0,
+                    y: 300,
+                    width: 400,
+                    height: 300,
+                },
+                Call::MoveResize {
+                    w: 6,
+                    x: 400,
+                    y: 300,
+                    width: 400,
+                    height: 300,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn clamping_keeps_rectangles_inside_the_area() {
+        assert_eq!(clamp_to_area(100, 100, 200, 200, 800, 600), (100, 100));
+        assert_eq!(clamp_to_area(-50, 500, 200, 200, 800, 600), (0, 400));
+        assert_eq!(clamp_to_area(700, -10, 200, 200, 800, 600), (600, 0));
+        assert_eq!(clamp_to_area(10, 10, 1000, 700, 800, 600), (0, 0));
+    }
+
+    #[test]
+    fn framing_keeps_the_content_in_place() {
+        let backend = MockBackend::new();
+        let (x, y) = requested_frame_position(100, 100, 200, 150, 3, 2, (800, 600), 10);
+        let frame = backend.create_frame(1, x, y, 204, 154, 3, 0, None);
+        reparent_into_frame(&backend, 2, frame, 2);
+
+        // The client sits inside the frame's border and inner border
+        assert_eq!(
+            backend.calls(),
+            vec![
+                Call::CreateFrame {
+                    parent: 1,
+                    x: 95,
+                    y: 95,
+                    width: 204,
+                    height: 154,
+                    border_width: 3,
+                },
+                Call::Reparent {
+                    w: 2,
+                    parent: 1000,
+                    x: 2,
+                    y: 2,
+                },
+            ]
+        );
+        assert_eq!(frame_position(100, 100, 0, 0), (100, 100));
+    }
+
+    #[test]
+    fn snaps_to_nearby_edges() {
+        let window = |x, y| Geometry {
+            x,
+            y,
+            width: 100,
+            height: 100,
+        };
+
+        // Close to the screen's top left corner
+        assert_eq!(snap(window(5, -7), &[], 800, 600, 10), (0, 0));
+        // The right edge snaps to the screen's right edge
+        assert_eq!(snap(window(695, 300), &[], 800, 600, 10), (700, 300));
+        // Too far away to snap
+        assert_eq!(snap(window(50, 50), &[], 800, 600, 10), (50, 50));
+        // Next to another window, but not to one that isn't beside it
+        let others = [window(300, 300), window(450, 0)];
+        assert_eq!(snap(window(206, 320), &others, 800, 600, 10), (200, 320));
+        assert_eq!(snap(window(206, 150), &others, 800, 600, 10), (206, 150));
+        // A distance of 0 only keeps windows that are already aligned
+        assert_eq!(snap(window(5, 0), &[], 800, 600, 0), (5, 0));
+    }
+
+    #[test]
+    fn unframing_keeps_the_content_in_place() {
+        let backend = MockBackend::new();
+        reparent_out_of_frame(&backend, 2, 1, (95, 95), 3, 2);
+        // A frame dragged partly off screen leaves the client there as well
+        reparent_out_of_frame(&backend, 3, 1, (-50, 20), 3, 0);
+
+        assert_eq!(
+            backend.calls(),
+            vec![
+                Call::Reparent {
+                    w: 2,
+                    parent: 1,
+                    x: 100,
+                    y: 100,
+                },
+                Call::Reparent {
+                    w: 3,
+                    parent: 1,
+                    x: -47,
+                    y: 23,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn windows_requested_off_screen_are_pulled_back() {
+        // 20 pixels of the 200x100 client and its 3 pixel border stay on screen
+        assert_eq!(
+            requested_frame_position(-500, -500, 200, 100, 3, 0, (800, 600), 20),
+            (-186, -86)
+        );
+        assert_eq!(
+            requested_frame_position(5000, 100, 200, 100, 3, 0, (800, 600), 20),
+            (780, 97)
+        );
+        // Positions on screen are only offset by the borders
+        assert_eq!(
+            requested_frame_position(100, 100, 200, 100, 3, 2, (800, 600), 20),
+            (95, 95)
+        );
+    }
+
+    #[test]
+    fn repeated_steps_accelerate_up_to_a_limit() {
+        assert_eq!(accelerated_step(20, 1.0, 50), 20);
+        assert_eq!(accelerated_step(20, 1.5, 0), 20);
+        assert_eq!(accelerated_step(20, 1.5, 1), 30);
+        assert_eq!(accelerated_step(20, 1.5, 2), 45);
+        assert_eq!(
+            accelerated_step(20, 1.5, 1000),
+            20 * MAX_ACCELERATION as u32
+        );
+    }
+
+    #[test]
+    fn partially_visible_rectangles_keep_an_edge_on_screen() {
+        assert_eq!(
+            keep_partially_visible(10, 10, 200, 100, 800, 600, 20),
+            (10, 10)
+        );
+        assert_eq!(
+            keep_partially_visible(790, 590, 200, 100, 800, 600, 20),
+            (780, 580)
+        );
+        assert_eq!(
+            keep_partially_visible(-500, -500, 200, 100, 800, 600, 20),
+            (-180, -80)
+        );
+    }
+
+    #[test]
+    fn nothing_to_tile() {
+        let backend = MockBackend::new();
+        tile(
+            &backend,
+            &[],
+            screen(800, 600),
+            Spacing {
+                border_width: 3,
+                ..Spacing::default()
+            },
+            0.5,
+            1,
+            SplitDirection::Vertical,
+        );
+
+        assert!(backend.calls().is_empty());
+    }
+}
@@ -1,78 +1,215 @@
-use log::{error, info, trace, warn};
-use std::ffi::{c_void, CStr};
+mod backend;
+mod client;
+mod colors;
+mod config;
+mod focus;
+mod grab;
+mod highlight;
+mod ipc;
+mod keys;
+mod layout;
+mod menu;
+mod monitor;
+mod process;
+mod resources;
+mod shape;
+mod switcher;
+mod wallpaper;
+
+use backend::{XBackend, XlibBackend};
+use client::{Client, ClientList};
+use colors::Colors;
+use config::{
+    ButtonAction, Config, KeyBinding, NamedWorkspace, NewWindowWorkspace, Placement, ResizeMode,
+    RotateFocus, SplitDirection,
+};
+use grab::ServerGrab;
+use highlight::MonitorHighlight;
+use ipc::Command as IpcCommand;
+use layout::{Geometry, Layout, Spacing};
+use log::{error, info, trace, warn, LevelFilter};
+use menu::WindowMenu;
+use monitor::Monitor;
+use resources::Resources;
+use std::env;
+use std::ffi::{c_void, CStr, CString};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
 use std::mem::MaybeUninit;
-use std::os::raw::c_uint;
+use std::os::raw::{c_char, c_int, c_long, c_uint};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
 use std::process::Command;
 use std::ptr::{self, NonNull};
 use std::sync::atomic::{AtomicBool, Ordering};
-use x11::keysym::{XK_Tab, XK_space, XK_Q, XK_R};
+use std::thread;
+use std::time::{Duration, Instant};
+use switcher::Switcher;
+use x11::keysym::{
+    XK_Alt_L, XK_Alt_R, XK_Down, XK_End, XK_Escape, XK_Home, XK_Left, XK_Meta_L, XK_Meta_R,
+    XK_Return, XK_Right, XK_Tab, XK_Up, XK_bracketleft, XK_bracketright, XK_comma, XK_equal,
+    XK_grave, XK_minus, XK_period, XK_space, XK_0, XK_1, XK_2, XK_3, XK_4, XK_5, XK_6, XK_7, XK_8,
+    XK_9, XK_A, XK_B, XK_C, XK_D, XK_G, XK_I, XK_J, XK_K, XK_L, XK_M, XK_Q, XK_R, XK_S, XK_T, XK_U,
+    XK_W, XK_X, XK_Z,
+};
 use x11::xlib::{
-    BadAccess, Button1, Button1Mask, ButtonMotionMask, ButtonPress, ButtonPressMask, ButtonRelease,
-    ButtonReleaseMask, ConfigureNotify, ConfigureRequest, CreateNotify, CurrentTime, DestroyNotify,
-    Display, GrabModeAsync, IsViewable, KeyPress, KeyRelease, MapRequest, Mod1Mask, MotionNotify,
-    ReparentNotify, RevertToPointerRoot, SubstructureNotifyMask, SubstructureRedirectMask,
-    UnmapNotify, Window, XAddToSaveSet, XButtonPressedEvent, XButtonReleasedEvent, XCloseDisplay,
-    XConfigureEvent, XConfigureRequestEvent, XConfigureWindow, XCreateSimpleWindow,
-    XCreateWindowEvent, XDefaultRootWindow, XDestroyWindow, XDestroyWindowEvent, XDisplayName,
-    XDisplayString, XErrorEvent, XFree, XGetGeometry, XGetInputFocus, XGetWindowAttributes,
-    XGrabButton, XGrabKey, XGrabServer, XKeyPressedEvent, XKeyReleasedEvent, XKeysymToKeycode,
-    XKillClient, XMapRequestEvent, XMapWindow, XMotionEvent, XMoveResizeWindow, XMoveWindow,
-    XNextEvent, XOpenDisplay, XQueryTree, XRaiseWindow, XRemoveFromSaveSet, XReparentEvent,
-    XReparentWindow, XSelectInput, XSetErrorHandler, XSetInputFocus, XSync, XUngrabServer,
-    XUnmapEvent, XUnmapWindow, XWindowAttributes, XWindowChanges,
+    Above, AnyButton, AnyKey, AnyModifier, Atom, BadAccess, ButtonMotionMask, ButtonPress,
+    ButtonPressMask, ButtonRelease, ButtonReleaseMask, CWSibling, CWStackMode, ClientMessage,
+    ClientMessageData, ConfigureNotify, ConfigureRequest, ControlMask, CreateNotify, CurrentTime,
+    DestroyNotify, Display, Expose, False, FocusChangeMask, FocusIn, FocusOut, GCForeground,
+    GCFunction, GCLineWidth, GCSubwindowMode, GXxor, GrabModeAsync, GrabSuccess, IncludeInferiors,
+    IsViewable, KeyPress, KeyRelease, KeySym, LockMask, MapRequest, MappingKeyboard,
+    MappingModifier, MappingNotify, Mod1Mask, Mod2Mask, MotionNotify, NoEventMask, NotifyGrab,
+    NotifyUngrab, PointerRoot, PropModeReplace, PropertyChangeMask, PropertyNotify, ReparentNotify,
+    ShiftMask, StructureNotifyMask, SubstructureNotifyMask, SubstructureRedirectMask, Success,
+    Time, True, UnmapNotify, Window, XAddToSaveSet, XButtonPressedEvent, XButtonReleasedEvent,
+    XChangeProperty, XCheckTypedWindowEvent, XClassHint, XClearWindow, XClientMessageEvent,
+    XCloseDisplay, XConfigureEvent, XConfigureRequestEvent, XConnectionNumber, XCreateGC,
+    XCreateWindowEvent, XDefaultRootWindow, XDeleteProperty, XDestroyWindowEvent, XDisplayName,
+    XDisplayString, XDrawRectangle, XErrorEvent, XEvent, XEventsQueued, XExposeEvent, XFetchName,
+    XFocusChangeEvent, XFree, XFreeGC, XGCValues, XGetClassHint, XGetErrorDatabaseText,
+    XGetErrorText, XGetGeometry, XGetInputFocus, XGetTransientForHint, XGetWMClientMachine,
+    XGetWMHints, XGetWMProtocols, XGetWindowAttributes, XGetWindowProperty, XGrabButton, XGrabKey,
+    XGrabKeyboard, XGrabPointer, XInternAtom, XKeyPressedEvent, XKeyReleasedEvent,
+    XKeysymToKeycode, XKillClient, XMapRequestEvent, XMappingEvent, XMotionEvent, XNextEvent,
+    XOpenDisplay, XPeekEvent, XPending, XPropertyEvent, XQueryPointer, XQueryTree,
+    XRefreshKeyboardMapping, XRemoveFromSaveSet, XReparentEvent, XSelectInput, XSendEvent,
+    XSetErrorHandler, XSetWindowBackground, XSetWindowBorder, XSetWindowBorderWidth, XSync,
+    XTextProperty, XUngrabButton, XUngrabKey, XUngrabKeyboard, XUngrabPointer, XUnmapEvent,
+    XUrgencyHint, XWindowAttributes, XWindowChanges, XkbAnyEvent, XkbKeycodeToKeysym, XkbMapNotify,
+    XkbMapNotifyEvent, XkbMapNotifyMask, XkbNewKeyboardNotify, XkbNewKeyboardNotifyMask,
+    XkbQueryExtension, XkbRefreshKeyboardMapping, XkbSelectEvents, GC, XA_ATOM, XA_CARDINAL,
+    XA_WINDOW, XA_WM_HINTS,
 };
 
-#[derive(Debug)]
-struct ClientList(Vec<(Window, Window)>);
+struct Atoms {
+    net_wm_state: Atom,
+    net_wm_state_hidden: Atom,
+    net_wm_state_sticky: Atom,
+    net_wm_state_above: Atom,
+    net_wm_state_maximized_horz: Atom,
+    net_wm_state_maximized_vert: Atom,
+    net_number_of_desktops: Atom,
+    net_current_desktop: Atom,
+    net_wm_desktop: Atom,
+    net_wm_window_type: Atom,
+    net_wm_window_type_dialog: Atom,
+    motif_wm_hints: Atom,
+    net_wm_pid: Atom,
+    net_wm_window_opacity: Atom,
+    net_wm_user_time: Atom,
+    net_client_list: Atom,
+    net_client_list_stacking: Atom,
+    net_showing_desktop: Atom,
+    net_frame_extents: Atom,
+    wm_protocols: Atom,
+    wm_delete_window: Atom,
+}
 
-impl ClientList {
-    pub fn new() -> Self {
-        Self(Vec::new())
-    }
+impl Atoms {
+    fn new(display: *mut Display) -> Self {
+        let intern = |name: &str| {
+            let name = CString::new(name).unwrap();
+            unsafe { XInternAtom(display, name.as_ptr(), False) }
+        };
 
-    pub fn len(&self) -> usize {
-        self.0.len()
+        Atoms {
+            net_wm_state: intern("_NET_WM_STATE"),
+            net_wm_state_hidden: intern("_NET_WM_STATE_HIDDEN"),
+            net_wm_state_sticky: intern("_NET_WM_STATE_STICKY"),
+            net_wm_state_above: intern("_NET_WM_STATE_ABOVE"),
+            net_wm_state_maximized_horz: intern("_NET_WM_STATE_MAXIMIZED_HORZ"),
+            net_wm_state_maximized_vert: intern("_NET_WM_STATE_MAXIMIZED_VERT"),
+            net_number_of_desktops: intern("_NET_NUMBER_OF_DESKTOPS"),
+            net_current_desktop: intern("_NET_CURRENT_DESKTOP"),
+            net_wm_desktop: intern("_NET_WM_DESKTOP"),
+            net_wm_window_type: intern("_NET_WM_WINDOW_TYPE"),
+            net_wm_window_type_dialog: intern("_NET_WM_WINDOW_TYPE_DIALOG"),
+            motif_wm_hints: intern("_MOTIF_WM_HINTS"),
+            net_wm_pid: intern("_NET_WM_PID"),
+            net_wm_window_opacity: intern("_NET_WM_WINDOW_OPACITY"),
+            net_wm_user_time: intern("_NET_WM_USER_TIME"),
+            net_client_list: intern("_NET_CLIENT_LIST"),
+            net_client_list_stacking: intern("_NET_CLIENT_LIST_STACKING"),
+            net_showing_desktop: intern("_NET_SHOWING_DESKTOP"),
+            net_frame_extents: intern("_NET_FRAME_EXTENTS"),
+            wm_protocols: intern("WM_PROTOCOLS"),
+            wm_delete_window: intern("WM_DELETE_WINDOW"),
+        }
     }
+}
 
-    pub fn contains(&self, w: &Window) -> bool {
-        self.0.iter().find(|(win, _)| win == w).is_some()
-    }
+/// Keys used to switch to and move windows to the workspace at the same index
+const WORKSPACE_KEYS: [c_uint; 10] = [XK_1, XK_2, XK_3, XK_4, XK_5, XK_6, XK_7, XK_8, XK_9, XK_0];
 
-    pub fn find(&self, w: &Window) -> Option<usize> {
-        self.0
-            .iter()
-            .enumerate()
-            .find(|(_, (win, _))| win == w)
-            .map(|(i, _)| i)
-    }
+/// Actions of a _NET_WM_STATE client message
+const NET_WM_STATE_REMOVE: i64 = 0;
+const NET_WM_STATE_ADD: i64 = 1;
+const NET_WM_STATE_TOGGLE: i64 = 2;
 
-    pub fn index(&self, i: usize) -> Option<(&Window, &Window)> {
-        self.0.get(i).map(|(w, f)| (w, f))
-    }
+/// Neither the master nor the stack area can be dragged narrower than this share of the screen
+const MIN_MASTER_RATIO: f32 = 0.1;
 
-    pub fn get(&self, w: &Window) -> Option<&Window> {
-        self.0.iter().find(|(win, _)| win == w).map(|(_, f)| f)
-    }
+/// How much Mod+K and Mod+J change a stacked window's size factor, and how far it can go
+const SIZE_FACTOR_STEP: f32 = 0.25;
+const MIN_SIZE_FACTOR: f32 = 0.25;
+const MAX_SIZE_FACTOR: f32 = 4.0;
 
-    pub fn insert(&mut self, w: Window, f: Window) {
-        self.0.push((w, f));
-    }
+/// How far each cascaded window is placed down and right of the previous one
+const CASCADE_STEP: i32 = 30;
+/// Spacing of the candidate positions tried by smart placement
+const SMART_PLACEMENT_STEP: u32 = 20;
 
-    pub fn remove(&mut self, w: &Window) {
-        if let Some(i) = self.find(w) {
-            self.0.remove(i);
-        }
-    }
-}
+/// Share of the monitor width and height a spotlit window is enlarged to
+const SPOTLIGHT_SIZE: f32 = 0.8;
+/// Opacity the other windows are dimmed to while one is spotlit
+const SPOTLIGHT_DIM: f32 = 0.5;
+
+/// How soon, in milliseconds, Mod+Ctrl+Q has to be pressed again to close the whole workspace
+const CLOSE_ALL_CONFIRM_TIME: Time = 1000;
+
+/// How soon, in milliseconds, a keyboard move or resize has to be repeated to be accelerated. Long
+/// enough to cover the delay before a held key starts repeating.
+const NUDGE_REPEAT_TIME: Time = 750;
+
+/// How long a control connection may take to send its command
+const IPC_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// How long a window that unmapped itself keeps its workspace and place for when it maps again
+const REMEMBER_UNMAPPED_FOR: Duration = Duration::from_secs(300);
+
+/// Keys that release Mod1, which closes the window switcher
+const MOD_KEYSYMS: [c_uint; 4] = [XK_Alt_L, XK_Alt_R, XK_Meta_L, XK_Meta_R];
+
+/// Device id of the core keyboard in XKB requests
+const XKB_USE_CORE_KBD: c_uint = 0x0100;
+
+/// XEventsQueued mode that only counts events already read, without flushing or reading more
+const QUEUED_ALREADY: c_int = 0;
+
+/// CapsLock and NumLock, which are ignored when matching bindings
+const IGNORED_MODIFIERS: c_uint = LockMask | Mod2Mask;
+const LOCK_MODIFIER_COMBINATIONS: [c_uint; 4] = [0, LockMask, Mod2Mask, LockMask | Mod2Mask];
+
+/// `_NET_WM_DESKTOP` value for windows that are shown on all desktops
+const ALL_DESKTOPS: u64 = 0xFFFFFFFF;
 
 fn main() {
+    // Everything is let through to stderrlog so the level can still be raised at runtime
     stderrlog::new()
         .module(module_path!())
-        .verbosity(10)
+        .verbosity(4)
         .init()
         .unwrap();
+    log::set_max_level(initial_log_level());
 
-    let wm = match WindowManager::new() {
+    // An explicit display name takes precedence over $DISPLAY
+    let wm = match env::args().nth(1) {
+        Some(display_name) => WindowManager::new_on_display(&display_name),
+        None => WindowManager::new(),
+    };
+    let wm = match wm {
         Some(wm) => wm,
         None => panic!("Failed to initialize window manager"),
     };
@@ -80,74 +217,615 @@ fn main() {
     wm.run();
 }
 
+/// Log levels from quietest to noisiest, stepped through by Mod+minus and Mod+equal
+const LOG_LEVELS: [LevelFilter; 6] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+/// The level named by `$WM_RS_LOG`, e.g. `debug`, or info by default
+fn initial_log_level() -> LevelFilter {
+    match env::var("WM_RS_LOG") {
+        Ok(level) => level.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid WM_RS_LOG level {:?}, using info", level);
+            LevelFilter::Info
+        }),
+        Err(_) => LevelFilter::Info,
+    }
+}
+
+/// Makes the log `steps` levels noisier, or quieter if negative
+fn adjust_log_level(steps: isize) {
+    let current = LOG_LEVELS
+        .iter()
+        .position(|&level| level == log::max_level())
+        .unwrap_or(3) as isize;
+    let level = LOG_LEVELS[(current + steps).clamp(0, LOG_LEVELS.len() as isize - 1) as usize];
+    log::set_max_level(level);
+    // Logged at error so it shows up at any level but Off
+    error!("Log level is now {}", level);
+}
+
+/// The unit direction of an arrow key, if `keysym` is one
+fn arrow_direction(keysym: KeySym) -> Option<(i32, i32)> {
+    [
+        (XK_Left, (-1, 0)),
+        (XK_Right, (1, 0)),
+        (XK_Up, (0, -1)),
+        (XK_Down, (0, 1)),
+    ]
+    .into_iter()
+    .find(|&(key, _)| keysym == key.into())
+    .map(|(_, direction)| direction)
+}
+
+fn spawn(program: &str) {
+    spawn_command(Command::new(program), program);
+}
+
+/// Runs `command` through `sh`, so it can have arguments and use shell syntax
+fn spawn_shell(command: &str) {
+    let mut sh = Command::new("sh");
+    sh.arg("-c").arg(command);
+    spawn_command(sh, command);
+}
+
+fn spawn_command(mut command: Command, description: &str) {
+    match command.spawn() {
+        // Reap the child in the background so it doesn't linger as a zombie
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(err) => error!("Failed to spawn {}: {}", description, err),
+    }
+}
+
+/// The size of `root`, or None if the server can't report a usable one
+fn root_size(display: *mut Display, root: Window) -> Option<(u32, u32)> {
+    let mut returned_root: Window = 0;
+    let (mut x, mut y) = (0, 0);
+    let (mut width, mut height, mut border_width, mut depth) = (0, 0, 0, 0);
+    let status = unsafe {
+        XGetGeometry(
+            display,
+            root,
+            &mut returned_root,
+            &mut x,
+            &mut y,
+            &mut width,
+            &mut height,
+            &mut border_width,
+            &mut depth,
+        )
+    };
+    (status != 0 && width > 0 && height > 0).then_some((width, height))
+}
+
+/// Loads the config file, with colors it leaves out taken from the X resources
+fn load_config(display: *mut Display) -> Config {
+    let resources = Resources::load(display);
+    Config::load(|name| resources.get(name))
+}
+
 pub struct WindowManager {
     display: NonNull<Display>,
+    backend: XlibBackend,
     root: Window,
+    config: Config,
+    /// Pixels of the configured colors
+    colors: Colors,
     clients: ClientList,
+    /// The monitors making up the screen, each showing a workspace of its own
+    monitors: Vec<Monitor>,
+    /// The monitor showing the current workspace, which is the one workspace commands act on
+    focused_monitor: usize,
+    /// Marks the focused monitor when there are several
+    highlight: MonitorHighlight,
+    /// The workspace that was shown before the current one, if another one has been shown yet
+    previous_workspace: Option<usize>,
+    atoms: Atoms,
+    screen_width: u32,
+    screen_height: u32,
     drag_pos_start: Option<(i32, i32)>,
     drag_frame_pos: Option<(i32, i32)>,
-    border_width: u32,
+    /// Size of the frame, without its border, when a resize drag started
+    drag_frame_size: Option<(u32, u32)>,
+    /// Set while resizing in outline mode
+    resize_outline: Option<ResizeOutline>,
+    borders_enabled: bool,
+    gaps_enabled: bool,
+    /// Share of the screen width, or height with a horizontal split, taken by the master window
+    master_ratio: f32,
+    /// Number of windows in the master column of each workspace
+    nmaster: Vec<usize>,
+    /// How the tiled windows of each workspace are arranged, Mod+Shift+T toggles the current one
+    layouts: Vec<Layout>,
+    /// The layout each workspace had before the last toggle, to switch back to
+    previous_layouts: Vec<Option<Layout>>,
+    /// How each workspace is split between the master and stack areas
+    split_directions: Vec<SplitDirection>,
+    /// Set while the boundary between the master and stack areas is being dragged
+    dragging_divider: bool,
+    /// Set while a window is being dragged by a move binding
+    moving_window: bool,
+    /// Set once the pointer has left the drag threshold since the button was pressed
+    drag_started: bool,
+    /// Open while Mod is held after Mod+Tab when the window switcher is enabled
+    switcher: Option<Switcher>,
+    /// The menu opened with Mod+W to pick a window from, while it runs
+    window_menu: Option<WindowMenu>,
+    /// While set, new windows float and closed ones leave a hole instead of the tiled windows
+    /// being rearranged
+    layout_locked: bool,
+    /// Where the last window placed by cascading went
+    last_cascade: Option<(i32, i32)>,
+    /// When Mod+Ctrl+Q was pressed to close every window on the workspace, waiting to be confirmed
+    close_all_requested: Option<Time>,
+    /// The key and modifiers of the last keyboard move or resize, when it was pressed and how many
+    /// times in a row it had been pressed before
+    last_nudge: Option<(KeySym, c_uint, Time, u32)>,
+    /// The control socket and its path, which is removed again on exit
+    ipc_listener: Option<(UnixListener, PathBuf)>,
+    /// The window enlarged with Mod+Z, if any
+    spotlight: Option<Spotlight>,
+    /// Clients that unmapped themselves recently, oldest first
+    recently_unmapped: Vec<Unmapped>,
+    /// The windows hidden to show the desktop with Mod+Shift+D, while it is shown
+    showing_desktop: bool,
+    /// Whether the server has the Shape extension needed for rounded corners
+    shape_supported: bool,
+    /// Event type of XKB events, if the server has the XKB extension
+    xkb_event_type: Option<c_int>,
+}
+
+/// A window temporarily enlarged in the middle of the screen, and what to restore it to
+struct Spotlight {
+    window: Window,
+    geometry: Geometry,
+    floating: bool,
+}
+
+/// Where a client was when it unmapped itself, like a window hidden to a tray, so it comes back
+/// there instead of being placed like a new window
+struct Unmapped {
+    window: Window,
+    workspace: usize,
+    floating: bool,
+    position: (i32, i32),
+    time: Instant,
+}
+
+/// XOR outline drawn on the root while resizing in outline mode
+struct ResizeOutline {
+    gc: GC,
+    /// The rectangle the outline currently covers
+    geometry: Geometry,
+    grab: ServerGrab,
 }
 
 static WM_DETECTED: AtomicBool = AtomicBool::new(false);
+/// Set by the SIGHUP handler and picked up by the event loop, which reloads the config
+static RELOAD_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sighup(_signal: c_int) {
+    RELOAD_REQUESTED.store(true, Ordering::Relaxed);
+}
 
 impl WindowManager {
+    /// Connects to the display named by `$DISPLAY`
     pub fn new() -> Option<Box<WindowManager>> {
-        let display = match NonNull::new(unsafe { XOpenDisplay(ptr::null()) }) {
+        Self::open(ptr::null())
+    }
+
+    /// Connects to the display `name`, e.g. `:1` for a nested Xephyr server
+    pub fn new_on_display(name: &str) -> Option<Box<WindowManager>> {
+        let name = match CString::new(name) {
+            Ok(name) => name,
+            Err(_) => {
+                error!("Invalid display name: {:?}", name);
+                return None;
+            }
+        };
+
+        Self::open(name.as_ptr())
+    }
+
+    fn open(display_name: *const c_char) -> Option<Box<WindowManager>> {
+        let display = match NonNull::new(unsafe { XOpenDisplay(display_name) }) {
             Some(display) => display,
             None => {
                 error!("Failed to open X display: {:?}", unsafe {
-                    CStr::from_ptr(XDisplayName(ptr::null()))
+                    CStr::from_ptr(XDisplayName(display_name))
                 });
                 return None;
             }
         };
 
         let root = unsafe { XDefaultRootWindow(display.as_ptr()) };
+        // A display that connects but can't answer for its root window would only fail later in
+        // some less obvious place
+        let (screen_width, screen_height) = match root_size(display.as_ptr(), root) {
+            Some(size) => size,
+            None => {
+                error!("Failed to get the geometry of the root window {}", root);
+                unsafe { XCloseDisplay(display.as_ptr()) };
+                return None;
+            }
+        };
+        let config = load_config(display.as_ptr());
+        let colors = Colors::alloc(display.as_ptr(), &config);
+        let master_ratio = config.master_ratio;
+        let nmaster = vec![1; config.workspaces];
+        let layouts = vec![Layout::Tile; config.workspaces];
+        let previous_layouts = vec![None; config.workspaces];
+        let split_directions = vec![config.split_direction; config.workspaces];
+        let atoms = Atoms::new(display.as_ptr());
+        let monitors = monitor::arrange(
+            monitor::query(display.as_ptr(), root, (screen_width, screen_height)),
+            &[],
+            config.workspaces,
+        );
+        info!("Found {} monitors: {:?}", monitors.len(), monitors);
+        // Work starts out on the primary monitor
+        let focused_monitor = monitor::primary(&monitors, config.primary_monitor);
+        let highlight =
+            MonitorHighlight::new(display.as_ptr(), root, colors.focused_monitor_border);
 
         Some(Box::new(WindowManager {
             display,
+            backend: XlibBackend::new(display),
             root,
+            config,
+            colors,
             clients: ClientList::new(),
+            monitors,
+            focused_monitor,
+            highlight,
+            previous_workspace: None,
+            atoms,
+            screen_width,
+            screen_height,
             drag_pos_start: None,
             drag_frame_pos: None,
-            border_width: 3,
+            drag_frame_size: None,
+            resize_outline: None,
+            borders_enabled: true,
+            gaps_enabled: true,
+            master_ratio,
+            nmaster,
+            layouts,
+            previous_layouts,
+            split_directions,
+            dragging_divider: false,
+            moving_window: false,
+            drag_started: false,
+            switcher: None,
+            window_menu: None,
+            layout_locked: false,
+            last_cascade: None,
+            close_all_requested: None,
+            last_nudge: None,
+            spotlight: None,
+            recently_unmapped: Vec::new(),
+            showing_desktop: false,
+            ipc_listener: None,
+            shape_supported: shape::is_supported(display.as_ptr()),
+            xkb_event_type: Self::query_xkb(display.as_ptr()),
         }))
     }
 
+    fn query_xkb(display: *mut Display) -> Option<c_int> {
+        let mut opcode = 0;
+        let mut event_base = 0;
+        let mut error_base = 0;
+        let mut major = 1;
+        let mut minor = 0;
+        let supported = unsafe {
+            XkbQueryExtension(
+                display,
+                &mut opcode,
+                &mut event_base,
+                &mut error_base,
+                &mut major,
+                &mut minor,
+            )
+        } != 0;
+        if !supported {
+            warn!(
+                "The XKB extension is missing, keyboard changes are only picked up from \
+                 MappingNotify"
+            );
+        }
+
+        supported.then_some(event_base)
+    }
+
     pub fn update_windows(&mut self) {
-        let win_count = self.clients.len();
-        if win_count == 0 {
+        if self.config.borders_on_focused_monitor_only {
+            self.update_border_widths();
+        }
+        let monocle = self
+            .monitors
+            .iter()
+            .any(|m| self.layouts[m.workspace] == Layout::Monocle);
+        let focused = if monocle { self.focused_client() } else { None };
+        let mut raised = false;
+        // Windows come and go from behind the one on top in monocle, or float out of it
+        for client in self.clients.iter().filter(|c| c.shown()) {
+            self.update_border_color(client.window);
+        }
+        for monitor in &self.monitors {
+            // Sticky windows go along with the workspace of their monitor, so they are only tiled
+            // on that one
+            let visible: Vec<(Window, Window, bool, f32)> = self
+                .clients
+                .iter()
+                .filter(|c| c.shown() && !c.floating && c.workspace == monitor.workspace)
+                .map(|c| (c.window, c.frame, c.decorated, c.size_factor))
+                .collect();
+            let spacing = Spacing {
+                border_width: self.frame_border_width_on(monitor.workspace),
+                ..self.spacing()
+            };
+
+            match self.layouts[monitor.workspace] {
+                Layout::Tile => layout::tile(
+                    &self.backend,
+                    &visible,
+                    monitor.geometry,
+                    spacing,
+                    self.master_ratio,
+                    self.nmaster[monitor.workspace],
+                    self.split_directions[monitor.workspace],
+                ),
+                Layout::Monocle => {
+                    layout::monocle(&self.backend, &visible, monitor.geometry, spacing);
+                    // The focused window is the one left in view
+                    if let Some(&(_, frame, ..)) = visible.iter().find(|v| Some(v.0) == focused) {
+                        self.backend.raise(frame);
+                        raised = true;
+                    }
+                }
+            }
+        }
+        if raised {
+            self.raise_above();
+        }
+    }
+
+    /// Raises `w` if it is tiled on a workspace in the monocle layout, where only the window on
+    /// top can be seen
+    fn raise_in_monocle(&self, w: Window) {
+        let frame = match self.clients.client(&w) {
+            Some(c) if !c.floating && self.layouts[c.workspace] == Layout::Monocle => c.frame,
+            _ => return,
+        };
+        self.backend.raise(frame);
+        self.raise_above();
+    }
+
+    /// The workspace shown on the focused monitor
+    fn current_workspace(&self) -> usize {
+        self.monitors[self.focused_monitor].workspace
+    }
+
+    /// The area of the focused monitor
+    fn current_area(&self) -> Geometry {
+        self.monitors[self.focused_monitor].geometry
+    }
+
+    /// The index of the primary monitor, where new windows open by default
+    fn primary_monitor(&self) -> usize {
+        monitor::primary(&self.monitors, self.config.primary_monitor)
+    }
+
+    /// The monitor showing `workspace`, if it is shown at all
+    fn monitor_showing(&self, workspace: usize) -> Option<usize> {
+        self.monitors.iter().position(|m| m.workspace == workspace)
+    }
+
+    /// Makes the workspace on `monitor` the current one
+    fn focus_monitor(&mut self, monitor: usize) {
+        if monitor == self.focused_monitor || monitor >= self.monitors.len() {
             return;
         }
+        self.focused_monitor = monitor;
+        let workspace = self.current_workspace();
+        self.set_cardinal(self.root, self.atoms.net_current_desktop, workspace as u64);
+        self.update_highlight();
+        // The borders move along to the newly focused monitor
+        if self.config.borders_on_focused_monitor_only {
+            self.update_windows();
+        }
+        trace!(
+            "Focused monitor {} showing workspace {}",
+            monitor,
+            workspace
+        );
+    }
 
-        unsafe {
-            XMoveResizeWindow(
-                self.display.as_ptr(),
-                *self.clients.index(0).unwrap().1,
-                0,
-                0,
-                if win_count == 1 { 800 } else { 400 } - 2 * self.border_width,
-                600 - 2 * self.border_width,
-            );
+    /// Moves the highlight to the focused monitor, or hides it when there is only one monitor or it
+    /// is disabled
+    fn update_highlight(&mut self) {
+        let width = self.config.focused_monitor_border_width;
+        if width == 0 || self.monitors.len() < 2 {
+            self.highlight.hide(self.display.as_ptr());
+            return;
+        }
+        let area = self.current_area();
+        self.highlight.show(self.display.as_ptr(), area, width);
+    }
+
+    /// Moves the floating windows on `workspace` that are on another monitor over to `monitor`,
+    /// keeping their place relative to the monitor's corner
+    fn move_floating_to(&self, workspace: usize, monitor: usize) {
+        let target = self.monitors[monitor].geometry;
+        for client in self
+            .clients
+            .iter()
+            .filter(|c| c.floating && c.workspace == workspace)
+        {
+            let geometry = self.get_geometry(client.frame);
+            let from = self.monitors[monitor::most_overlapping(&self.monitors, &geometry)].geometry;
+            if from == target {
+                continue;
+            }
+            let (dx, dy) = (target.x - from.x, target.y - from.y);
+            self.backend
+                .move_window(client.frame, geometry.x + dx, geometry.y + dy);
         }
+    }
 
-        if win_count == 1 {
+    /// Reads the monitor layout again after the screen changed, keeping the workspaces they show
+    fn update_monitors(&mut self) {
+        let found = monitor::query(
+            self.display.as_ptr(),
+            self.root,
+            (self.screen_width, self.screen_height),
+        );
+        let monitors = monitor::arrange(found, &self.monitors, self.config.workspaces);
+        if monitors == self.monitors {
             return;
         }
+        info!("Monitors changed to {:?}", monitors);
+
+        let focused = self.current_workspace();
+        self.monitors = monitors;
+        // The current workspace stays current if its monitor is still there
+        self.focused_monitor = self
+            .monitor_showing(focused)
+            .unwrap_or_else(|| self.primary_monitor());
+        let current = self.current_workspace();
+        for i in 0..self.clients.len() {
+            let client = self.clients.index(i).unwrap();
+            if !client.shown() {
+                continue;
+            }
+            let (w, frame, sticky, floating) =
+                (client.window, client.frame, client.sticky, client.floating);
+            let mut workspace = client.workspace;
+            // Sticky windows are kept in view on the current monitor
+            if sticky && self.monitor_showing(workspace).is_none() {
+                workspace = current;
+                self.clients.client_mut(&w).unwrap().workspace = current;
+            }
+            match self.monitor_showing(workspace) {
+                Some(monitor) if floating => {
+                    // Floating windows left on a monitor that went away are brought back on screen
+                    let geometry = self.get_geometry(frame);
+                    let area = self.monitors[monitor].geometry;
+                    if self
+                        .monitors
+                        .iter()
+                        .all(|m| m.geometry.overlap(&geometry) == 0)
+                    {
+                        self.backend.move_window(frame, area.x, area.y);
+                    }
+                    self.backend.map(frame);
+                }
+                Some(_) => self.backend.map(frame),
+                None => self.backend.unmap(frame),
+            }
+        }
+        self.set_cardinal(self.root, self.atoms.net_current_desktop, current as u64);
+        self.update_highlight();
+    }
+
+    fn spacing(&self) -> Spacing {
+        Spacing {
+            border_width: self.frame_border_width(),
+            inner_border_width: self.inner_border_width(),
+            gap: if self.gaps_enabled {
+                self.config.gap
+            } else {
+                0
+            },
+            smart_gaps: self.config.smart_gaps,
+        }
+    }
+
+    /// Width of the frame's own border, which is drawn in the outer border color
+    fn frame_border_width(&self) -> u32 {
+        if self.borders_enabled {
+            self.config.outer_border_width
+        } else {
+            0
+        }
+    }
+
+    /// Gap between the frame and the client, which shows the frame's background in the inner
+    /// border color
+    fn inner_border_width(&self) -> u32 {
+        if self.borders_enabled {
+            self.config.inner_border_width
+        } else {
+            0
+        }
+    }
+
+    /// Border widths of a particular client, which are 0 if it doesn't want decorations
+    fn client_border_widths(&self, decorated: bool) -> (u32, u32) {
+        if decorated {
+            (self.frame_border_width(), self.inner_border_width())
+        } else {
+            (0, 0)
+        }
+    }
+
+    /// Width of the frame border of windows on `workspace`, which is left out on the monitors that
+    /// aren't focused with `borders_on_focused_monitor_only`
+    fn frame_border_width_on(&self, workspace: usize) -> u32 {
+        let unfocused =
+            workspace != self.current_workspace() && self.monitor_showing(workspace).is_some();
+        if self.config.borders_on_focused_monitor_only && unfocused {
+            0
+        } else {
+            self.frame_border_width()
+        }
+    }
+
+    /// The border widths the frame of `client` has where it is now
+    fn border_widths_of(&self, client: &Client) -> (u32, u32) {
+        if client.decorated {
+            (
+                self.frame_border_width_on(client.workspace),
+                self.inner_border_width(),
+            )
+        } else {
+            (0, 0)
+        }
+    }
 
-        let step =
-            (600 - 2 * (win_count as i32 - 1) * self.border_width as i32) / (win_count as i32 - 1);
-        for wi in 1..win_count {
-            let (&w, &f) = self.clients.index(wi).unwrap();
-            let y = step * (wi as i32 - 1) + self.border_width as i32 * 2 * (wi as i32 - 1);
+    /// Gives every frame the border width it should have where it is now
+    fn update_border_widths(&self) {
+        for client in self.clients.iter() {
+            let (border_width, _) = self.border_widths_of(client);
             unsafe {
-                XMoveResizeWindow(self.display.as_ptr(), f, 400, y, 400 - 2 * self.border_width, step as u32);
-                XMoveResizeWindow(self.display.as_ptr(), w, 0, 0, 400 - 2 * self.border_width, step as u32);
+                XSetWindowBorderWidth(self.display.as_ptr(), client.frame, border_width);
             }
+            self.update_frame_extents(client);
         }
     }
 
+    fn toggle_borders(&mut self) {
+        self.borders_enabled = !self.borders_enabled;
+        self.update_border_widths();
+        info!(
+            "Borders {}",
+            if self.borders_enabled {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+
+        self.update_windows();
+    }
+
     pub fn run(mut self) {
         WM_DETECTED.store(false, Ordering::Relaxed);
 
@@ -156,7 +834,8 @@ impl WindowManager {
             XSelectInput(
                 self.display.as_ptr(),
                 self.root,
-                SubstructureRedirectMask | SubstructureNotifyMask,
+                // StructureNotify reports the root window itself being resized
+                SubstructureRedirectMask | SubstructureNotifyMask | StructureNotifyMask,
             );
 
             XSync(self.display.as_ptr(), 0);
@@ -171,45 +850,71 @@ impl WindowManager {
 
             XSetErrorHandler(Some(WindowManager::on_x_error));
         }
-
-        unsafe {
-            XGrabServer(self.display.as_ptr());
+        self.open_ipc();
+        if let Some(color) = &self.config.wallpaper {
+            wallpaper::set(self.display.as_ptr(), color);
         }
-        let mut returned_root = 0;
-        let mut returned_parent = 0;
-        let mut top_level_windows: *mut u64 = std::ptr::null_mut();
-        let mut num_top_level_windows = 0;
-
-        let status = unsafe {
-            XQueryTree(
-                self.display.as_ptr(),
-                self.root,
-                &mut returned_root,
-                &mut returned_parent,
-                &mut top_level_windows,
-                &mut num_top_level_windows,
-            )
-        };
-        assert_ne!(status, 0);
-        assert_eq!(returned_root, self.root);
 
-        unsafe {
+        // Nothing may change the window tree while the existing windows are adopted
+        {
+            let start = Instant::now();
+            let _grab = ServerGrab::new(self.display);
+            self.adopt_existing_windows();
+            // Tiling once after framing everything keeps a busy startup linear
+            self.update_windows();
             info!(
-                "There were {} windows already existing",
-                num_top_level_windows
+                "Adopted {} windows in {:?}",
+                self.clients.len(),
+                start.elapsed()
             );
-            for i in 0..num_top_level_windows {
-                self.frame(ptr::read(top_level_windows.add(i as usize)), true);
-            }
+        }
 
-            XFree(top_level_windows as *mut c_void);
-            XUngrabServer(self.display.as_ptr());
+        if self.config.workspaces > WORKSPACE_KEYS.len() {
+            warn!(
+                "Only the first {} of {} workspaces have keybindings",
+                WORKSPACE_KEYS.len(),
+                self.config.workspaces
+            );
         }
+        if self.xkb_event_type.is_some() {
+            let mask = XkbNewKeyboardNotifyMask | XkbMapNotifyMask;
+            unsafe {
+                XkbSelectEvents(self.display.as_ptr(), XKB_USE_CORE_KBD, mask, mask);
+            }
+        }
+        self.regrab_keys();
 
-        self.grab_key(Mod1Mask, XK_space, self.root);
-        self.grab_key(Mod1Mask, XK_R, self.root);
+        self.set_cardinal(
+            self.root,
+            self.atoms.net_number_of_desktops,
+            self.config.workspaces as u64,
+        );
+        self.set_cardinal(
+            self.root,
+            self.atoms.net_current_desktop,
+            self.current_workspace() as u64,
+        );
+        self.update_highlight();
+
+        self.autostart();
+
+        unsafe {
+            libc::signal(
+                libc::SIGHUP,
+                on_sighup as extern "C" fn(c_int) as libc::sighandler_t,
+            );
+        }
 
         loop {
+            // Commands from the control socket are handled while waiting for X events
+            while unsafe { XPending(self.display.as_ptr()) } == 0 {
+                self.wait_for_input();
+                // A SIGHUP interrupts the wait
+                if RELOAD_REQUESTED.swap(false, Ordering::Relaxed) {
+                    self.reload_config();
+                }
+            }
+
             let e = unsafe {
                 let mut e = MaybeUninit::uninit();
                 XNextEvent(self.display.as_ptr(), e.as_mut_ptr());
@@ -230,244 +935,3032 @@ impl WindowManager {
                 MotionNotify => self.on_motion_notify(XMotionEvent::from(e)),
                 KeyPress => self.on_key_pressed(XKeyPressedEvent::from(e)),
                 KeyRelease => self.on_key_released(XKeyReleasedEvent::from(e)),
+                MappingNotify => self.on_mapping_notify(XMappingEvent::from(e)),
+                PropertyNotify => self.on_property_notify(XPropertyEvent::from(e)),
+                FocusIn => self.on_focus_in(XFocusChangeEvent::from(e)),
+                FocusOut => self.on_focus_out(XFocusChangeEvent::from(e)),
+                ClientMessage => self.on_client_message(XClientMessageEvent::from(e)),
+                Expose => self.on_expose(XExposeEvent::from(e)),
+                t if Some(t) == self.xkb_event_type => self.on_xkb_event(e),
                 _ => warn!("Ignored event: {}", e.get_type()),
             }
         }
     }
 
-    fn on_motion_notify(&mut self, e: XMotionEvent) {
-        assert!(self.clients.contains(&e.window));
-        assert!(self.drag_pos_start.is_some());
-        assert!(self.drag_frame_pos.is_some());
-        let frame = *self.clients.get(&e.window).unwrap();
-        let drag_pos_start = self.drag_pos_start.unwrap();
-        let delta = (e.x_root - drag_pos_start.0, e.y_root - drag_pos_start.1);
-
-        if e.state & Button1Mask != 0 {
-            let start_frame_pos = self.drag_frame_pos.unwrap();
-            let new_frame_pos = (start_frame_pos.0 + delta.0, start_frame_pos.1 + delta.1);
-            unsafe {
-                XMoveWindow(
-                    self.display.as_ptr(),
-                    frame,
-                    new_frame_pos.0,
-                    new_frame_pos.1,
-                );
+    /// Listens for commands on the control socket, see `ipc::Command` for what it accepts
+    fn open_ipc(&mut self) {
+        let display_name = unsafe { CStr::from_ptr(XDisplayString(self.display.as_ptr())) };
+        let path = ipc::socket_path(&display_name.to_string_lossy());
+        // A socket left behind by an earlier run would make binding fail
+        let _ = fs::remove_file(&path);
+        match UnixListener::bind(&path).and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        }) {
+            Ok(listener) => {
+                info!("Listening for commands on {:?}", path);
+                self.ipc_listener = Some((listener, path));
             }
+            Err(err) => warn!("Failed to open the control socket {:?}: {}", path, err),
         }
     }
 
-    fn on_button_pressed(&mut self, e: XButtonPressedEvent) {
-        assert!(self.clients.contains(&e.window));
-        let frame = *self.clients.get(&e.window).unwrap();
+    /// Blocks until the X connection, the control socket or the window menu has something to
+    /// read, and handles a pending command or menu choice
+    fn wait_for_input(&mut self) {
+        let poll_fd = |fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // poll skips negative descriptors, which keeps the indices of the others fixed
+        let mut fds = [
+            poll_fd(unsafe { XConnectionNumber(self.display.as_ptr()) }),
+            poll_fd(
+                self.ipc_listener
+                    .as_ref()
+                    .map_or(-1, |(listener, _)| listener.as_raw_fd()),
+            ),
+            poll_fd(
+                self.window_menu
+                    .as_ref()
+                    .and_then(|menu| menu.fd())
+                    .unwrap_or(-1),
+            ),
+        ];
 
-        self.drag_pos_start = Some((e.x_root, e.y_root));
+        // Interrupted polls are simply retried by the caller
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) } <= 0 {
+            return;
+        }
+        if fds[1].revents & libc::POLLIN != 0 {
+            self.accept_ipc();
+        }
+        // The menu closing its output without a choice shows up as a hangup
+        if fds[2].revents & (libc::POLLIN | libc::POLLHUP) != 0 {
+            self.finish_window_menu();
+        }
+    }
 
-        let mut returned_root: Window = 0;
-        let mut x: i32 = 0;
-        let mut y: i32 = 0;
-        let mut width: u32 = 0;
-        let mut height: u32 = 0;
-        let mut border_width: u32 = 0;
-        let mut depth: u32 = 0;
-        unsafe {
-            XGetGeometry(
-                self.display.as_ptr(),
-                frame,
-                &mut returned_root,
-                &mut x,
-                &mut y,
-                &mut width,
-                &mut height,
-                &mut border_width,
-                &mut depth,
-            );
+    /// Lists the windows on every workspace in the configured menu program
+    fn open_window_menu(&mut self) {
+        if self.window_menu.is_some() {
+            return;
+        }
+        let entries: Vec<(Window, String)> = self
+            .clients
+            .iter()
+            .filter(|c| !c.hidden && !c.no_focus)
+            .map(|c| (c.window, self.get_title(c.window)))
+            .collect();
+        if entries.is_empty() {
+            return;
         }
-        self.drag_frame_pos = Some((x, y));
+        match WindowMenu::open(&self.config.window_menu, entries) {
+            Ok(menu) => self.window_menu = Some(menu),
+            Err(err) => error!("Failed to spawn {}: {}", self.config.window_menu, err),
+        }
+    }
 
-        unsafe {
-            XRaiseWindow(self.display.as_ptr(), frame);
-            XSetInputFocus(
-                self.display.as_ptr(),
-                e.window,
-                RevertToPointerRoot,
-                CurrentTime,
-            );
+    /// Focuses the window picked from the menu, switching to its workspace if needed
+    fn finish_window_menu(&mut self) {
+        let chosen = match self.window_menu.take().and_then(WindowMenu::finish) {
+            Some(w) => w,
+            None => return,
+        };
+        // The window may have closed or been iconified while the menu was open
+        match self.clients.client(&chosen) {
+            Some(client) if !client.hidden => self.focus_window(chosen),
+            _ => info!("Window {} chosen from the menu is gone", chosen),
         }
     }
 
-    fn on_button_released(&mut self, _e: XButtonReleasedEvent) {
-        self.drag_frame_pos = None;
-        self.drag_pos_start = None;
+    fn accept_ipc(&mut self) {
+        let stream = match &self.ipc_listener {
+            Some((listener, _)) => match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    warn!("Failed to accept a control connection: {}", err);
+                    return;
+                }
+            },
+            None => return,
+        };
+        // A client that never finishes its line mustn't freeze the window manager
+        let _ = stream.set_nonblocking(false);
+        let _ = stream.set_read_timeout(Some(IPC_TIMEOUT));
+
+        let mut line = String::new();
+        let reply = match BufReader::new(&stream).read_line(&mut line) {
+            Ok(_) => match IpcCommand::parse(&line).and_then(|command| self.run_command(command)) {
+                Ok(output) => format!("ok\n{}", output),
+                Err(err) => format!("err {}\n", err),
+            },
+            Err(err) => format!("err {}\n", err),
+        };
+        trace!("Control command {:?}: {}", line.trim(), reply.trim());
+        let _ = (&stream).write_all(reply.as_bytes());
     }
 
-    fn on_key_pressed(&mut self, e: XKeyPressedEvent) {
-        info!("key pressed: {}", e.keycode);
-        let mut w = 0;
-        let mut focus_state = 0;
-        unsafe {
-            XGetInputFocus(self.display.as_ptr(), &mut w, &mut focus_state);
-        }
-        trace!("current focused window: {}", w);
-        trace!("event window: {}", e.window);
-        trace!("root window: {}", self.root);
-        if e.keycode == unsafe { XKeysymToKeycode(self.display.as_ptr(), XK_Q.into()) }.into() {
-            // Kill client
-            info!("Killing window {}", e.window);
-            unsafe {
-                XKillClient(self.display.as_ptr(), e.window);
+    /// Runs `command`, returning what it has to say after the `ok` line
+    fn run_command(&mut self, command: IpcCommand) -> Result<String, String> {
+        let focused = || self.focused_client().ok_or("no window is focused");
+        match command {
+            IpcCommand::Workspace(workspace) => {
+                if workspace >= self.config.workspaces {
+                    return Err(format!("there is no workspace {}", workspace));
+                }
+                self.view_workspace(workspace);
             }
-        } else if e.state & Mod1Mask != 0
-            && e.keycode == unsafe { XKeysymToKeycode(self.display.as_ptr(), XK_Tab.into()) }.into()
-        {
-            trace!("clients: {:?}", self.clients);
-            let mut w = 0;
-            let mut focus_state = 0;
-            unsafe {
-                XGetInputFocus(self.display.as_ptr(), &mut w, &mut focus_state);
+            IpcCommand::Focus(w) => match self.clients.client(&w) {
+                Some(client) if client.hidden => return Err(format!("{} is iconified", w)),
+                Some(_) => self.focus_window(w),
+                None => return Err(format!("{} is not a managed window", w)),
+            },
+            IpcCommand::Close => {
+                let w = focused()?;
+                self.close(w);
             }
-            trace!("current focused window: {}", w);
-            trace!("event window: {}", e.window);
-            trace!("root window: {}", self.root);
-            let i = self.clients.find(&e.window).unwrap();
-            let i = (i + 1) % self.clients.len();
-            let (&w, &f) = self.clients.index(i).unwrap();
-
-            unsafe {
-                XRaiseWindow(self.display.as_ptr(), f);
-                XSetInputFocus(self.display.as_ptr(), w, RevertToPointerRoot, CurrentTime);
+            IpcCommand::Iconify => {
+                let w = focused()?;
+                self.iconify(w);
+            }
+            IpcCommand::Zoom => {
+                let w = focused()?;
+                self.zoom(w);
+            }
+            IpcCommand::Retile => self.update_windows(),
+            IpcCommand::ResetLayout => self.reset_layout(),
+            IpcCommand::ToggleLayoutLock => self.toggle_layout_lock(),
+            IpcCommand::Reload => self.reload_config(),
+            IpcCommand::Monitors => {
+                return Ok(monitor::describe(
+                    &self.monitors,
+                    self.primary_monitor(),
+                    self.focused_monitor,
+                ))
             }
-        } else if e.state & Mod1Mask != 0
-            && e.keycode
-                == unsafe { XKeysymToKeycode(self.display.as_ptr(), XK_space.into()) }.into()
-        {
-            Command::new("/home/ole/dotfiles/bin/dmenu_run_history")
-                .spawn()
-                .unwrap();
-        } else if e.state & Mod1Mask != 0
-            && e.keycode == unsafe { XKeysymToKeycode(self.display.as_ptr(), XK_R.into()) }.into()
-        {
-            trace!("Updating window positions/sizes");
-            self.update_windows();
         }
+        Ok(String::new())
     }
 
-    fn on_key_released(&mut self, e: XKeyReleasedEvent) {
-        info!("key released: {}", e.keycode);
-    }
+    /// Frames the windows that were mapped before the window manager started
+    fn adopt_existing_windows(&mut self) {
+        let windows = match self.query_children(self.root) {
+            Some(windows) => windows,
+            None => {
+                error!("Failed to query the existing windows");
+                return;
+            }
+        };
 
-    fn frame(&mut self, w: Window, created_before_wm: bool) {
-        const BORDER_COLOR: u64 = 0xFF00FF;
-        const BG_COLOR: u64 = 0x0000FF;
+        info!("There were {} windows already existing", windows.len());
+        for w in windows {
+            if !self.has_class(w, &self.config.unmanaged_classes) {
+                self.frame(w, true);
+            }
+        }
+    }
+
+    /// The children of `w` from the bottom of the stack to the top, if they could be queried
+    fn query_children(&self, w: Window) -> Option<Vec<Window>> {
+        let mut returned_root = 0;
+        let mut returned_parent = 0;
+        let mut children: *mut u64 = std::ptr::null_mut();
+        let mut num_children = 0;
+
+        let status = unsafe {
+            XQueryTree(
+                self.display.as_ptr(),
+                w,
+                &mut returned_root,
+                &mut returned_parent,
+                &mut children,
+                &mut num_children,
+            )
+        };
+        if status == 0 {
+            return None;
+        }
+        if children.is_null() {
+            return Some(Vec::new());
+        }
+
+        let windows =
+            unsafe { std::slice::from_raw_parts(children, num_children as usize) }.to_vec();
+        unsafe {
+            XFree(children as *mut c_void);
+        }
+        Some(windows)
+    }
+
+    /// Publishes the managed windows on the root for pagers and compositors in _NET_CLIENT_LIST,
+    /// in tiling order as of the last time a window was managed or unmanaged
+    fn update_client_list(&self) {
+        let clients: Vec<Window> = self.clients.iter().map(|c| c.window).collect();
+        self.set_window_list(self.root, self.atoms.net_client_list, &clients);
+    }
+
+    /// Publishes the managed windows from the bottom of the stack to the top in
+    /// _NET_CLIENT_LIST_STACKING, after they were restacked
+    fn update_stacking_list(&self) {
+        // The frames are what is stacked, so the real order comes from the server
+        let stacking: Vec<Window> = self
+            .query_children(self.root)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|frame| self.clients.iter().find(|c| c.frame == frame))
+            .map(|c| c.window)
+            .collect();
+        self.set_window_list(self.root, self.atoms.net_client_list_stacking, &stacking);
+    }
+
+    /// Starts the configured autostart commands and the autostart script, if there is one
+    fn autostart(&self) {
+        for command in &self.config.autostart {
+            info!("Autostarting {}", command);
+            spawn_shell(command);
+        }
+
+        if let Some(script) = Config::autostart_script().filter(|script| script.is_file()) {
+            info!("Running autostart script {:?}", script);
+            let mut sh = Command::new("sh");
+            sh.arg(&script);
+            spawn_command(sh, &script.to_string_lossy());
+        }
+    }
+
+    fn on_motion_notify(&mut self, mut e: XMotionEvent) {
+        // Only the latest position matters, so skip over any motion that has queued up since
+        unsafe {
+            let mut next = MaybeUninit::uninit();
+            while XCheckTypedWindowEvent(
+                self.display.as_ptr(),
+                e.window,
+                MotionNotify,
+                next.as_mut_ptr(),
+            ) != 0
+            {
+                e = XMotionEvent::from(next.assume_init());
+            }
+        }
+
+        // Motion can still arrive after the button was released or the window went away
+        let frame = match self.clients.get(&e.window) {
+            Some(&frame) => frame,
+            None => {
+                trace!("Ignored motion over unmanaged window {}", e.window);
+                return;
+            }
+        };
+        let (drag_pos_start, start_frame_pos) = match (self.drag_pos_start, self.drag_frame_pos) {
+            (Some(pos), Some(frame_pos)) => (pos, frame_pos),
+            _ => {
+                trace!("Ignored motion over window {} without a drag", e.window);
+                return;
+            }
+        };
+        let delta = (e.x_root - drag_pos_start.0, e.y_root - drag_pos_start.1);
+
+        // Jitter while clicking shouldn't nudge the window
+        let threshold = self.config.drag_threshold as i32;
+        if !self.drag_started && delta.0.abs() <= threshold && delta.1.abs() <= threshold {
+            return;
+        }
+        self.drag_started = true;
+
+        if self.dragging_divider {
+            let area = self.current_area();
+            let (position, length) = match self.split_directions[self.current_workspace()] {
+                SplitDirection::Vertical => (e.x_root - area.x, area.width),
+                SplitDirection::Horizontal => (e.y_root - area.y, area.height),
+            };
+            let ratio = layout::master_ratio_at(position, length, self.spacing());
+            self.master_ratio = ratio.clamp(MIN_MASTER_RATIO, 1.0 - MIN_MASTER_RATIO);
+            self.update_windows();
+        } else if self.moving_window {
+            let mut new_frame_pos = (start_frame_pos.0 + delta.0, start_frame_pos.1 + delta.1);
+            let old_frame_pos = self.get_geometry(frame);
+            let floating = self.clients.client(&e.window).is_some_and(|c| c.floating);
+            if floating && self.config.snap_distance > 0 && e.state & ShiftMask == 0 {
+                new_frame_pos = self.snap(frame, new_frame_pos, old_frame_pos);
+            }
+            self.backend
+                .move_window(frame, new_frame_pos.0, new_frame_pos.1);
+            self.move_transients(
+                e.window,
+                new_frame_pos.0 - old_frame_pos.x,
+                new_frame_pos.1 - old_frame_pos.y,
+            );
+        } else if let Some((start_width, start_height)) = self.drag_frame_size {
+            let width = (start_width as i32 + delta.0).max(1) as u32;
+            let height = (start_height as i32 + delta.1).max(1) as u32;
+
+            if let Some(ResizeOutline {
+                gc,
+                geometry: outline,
+                ..
+            }) = self.resize_outline
+            {
+                // Drawing the same XOR rectangle again erases it
+                let (border_width, _) = self.client_border_widths(self.is_decorated(e.window));
+                let new_outline = Geometry {
+                    x: start_frame_pos.0,
+                    y: start_frame_pos.1,
+                    width: width + 2 * border_width,
+                    height: height + 2 * border_width,
+                };
+                self.draw_outline(gc, outline);
+                self.draw_outline(gc, new_outline);
+                self.resize_outline.as_mut().unwrap().geometry = new_outline;
+            } else {
+                self.resize_frame(
+                    e.window,
+                    start_frame_pos.0,
+                    start_frame_pos.1,
+                    width,
+                    height,
+                );
+            }
+        }
+    }
+
+    /// Where the dragged `frame` ends up at `position` after snapping to the screen and monitor
+    /// edges and the other windows on the workspace
+    fn snap(&self, frame: Window, position: (i32, i32), current: Geometry) -> (i32, i32) {
+        // The edges between monitors are snapped to like the edges of windows
+        let others: Vec<Geometry> = self
+            .clients
+            .visible(self.current_workspace())
+            .filter(|c| c.frame != frame)
+            .map(|c| self.get_geometry(c.frame))
+            .chain(self.monitors.iter().map(|m| m.geometry))
+            .collect();
+        let moving = Geometry {
+            x: position.0,
+            y: position.1,
+            ..current
+        };
+        layout::snap(
+            moving,
+            &others,
+            self.screen_width,
+            self.screen_height,
+            self.config.snap_distance,
+        )
+    }
+
+    fn on_button_pressed(&mut self, e: XButtonPressedEvent) {
+        // Presses can still arrive for a window that went away
+        let Some(client) = self.clients.client(&e.window) else {
+            trace!("Ignored button press on unmanaged window {}", e.window);
+            return;
+        };
+        let frame = client.frame;
+        let floating = client.floating;
+        // The divider and the layout the window is dragged in are those of its monitor
+        if let Some(monitor) = self.monitor_showing(client.workspace) {
+            self.focus_monitor(monitor);
+        }
+        let (border_width, _) = self.border_widths_of(self.clients.client(&e.window).unwrap());
+
+        let state = e.state & !IGNORED_MODIFIERS;
+        let action = match self
+            .config
+            .buttons
+            .iter()
+            .find(|b| b.button.modifiers == state && b.button.button == e.button)
+        {
+            Some(binding) => binding.action.clone(),
+            None => return,
+        };
+        match action {
+            ButtonAction::Close => {
+                self.close(e.window);
+                return;
+            }
+            ButtonAction::Command(command) => {
+                spawn_shell(&command);
+                return;
+            }
+            ButtonAction::Move | ButtonAction::Resize => {}
+        }
+
+        self.drag_pos_start = Some((e.x_root, e.y_root));
+
+        let geometry = self.get_geometry(frame);
+        self.drag_frame_pos = Some((geometry.x, geometry.y));
+
+        // Grabbing a tiled window next to the divider drags the divider instead of the window
+        if action == ButtonAction::Move && !floating && self.near_divider(e.x_root, e.y_root) {
+            self.dragging_divider = true;
+            self.focus(e.window);
+            return;
+        }
+        self.moving_window = action == ButtonAction::Move;
+
+        // Only floating windows can be resized, tiled ones get their size from the layout
+        if action == ButtonAction::Resize && floating {
+            self.drag_frame_size = Some((
+                layout::inner_size(geometry.width, border_width),
+                layout::inner_size(geometry.height, border_width),
+            ));
+            if self.config.resize_mode == ResizeMode::Outline {
+                self.start_resize_outline(e.window, geometry);
+            }
+        }
+
+        if self.config.raise_on_click {
+            self.backend.raise(frame);
+            self.raise_above();
+        }
+        self.focus(e.window);
+    }
+
+    fn on_button_released(&mut self, e: XButtonReleasedEvent) {
+        let was_dragging = self.drag_pos_start.is_some();
+        let was_moving = std::mem::take(&mut self.moving_window);
+        self.drag_started = false;
+        let drag_frame_pos = self.drag_frame_pos.take();
+        self.drag_pos_start = None;
+        self.drag_frame_size = None;
+
+        if !was_dragging {
+            return;
+        }
+
+        if self.dragging_divider {
+            self.dragging_divider = false;
+            info!("Master ratio is now {:.2}", self.master_ratio);
+            return;
+        }
+
+        // An outline resize only applies the size it ended with
+        if let Some(ResizeOutline {
+            gc,
+            geometry: outline,
+            grab,
+        }) = self.resize_outline.take()
+        {
+            self.draw_outline(gc, outline);
+            unsafe {
+                XFreeGC(self.display.as_ptr(), gc);
+            }
+            drop(grab);
+            unsafe {
+                XUngrabPointer(self.display.as_ptr(), CurrentTime);
+            }
+            let (border_width, _) = self.client_border_widths(self.is_decorated(e.window));
+            let (x, y) = drag_frame_pos.unwrap();
+            self.resize_frame(
+                e.window,
+                x,
+                y,
+                layout::inner_size(outline.width, border_width),
+                layout::inner_size(outline.height, border_width),
+            );
+            return;
+        }
+
+        if was_moving {
+            self.drop_on_monitor(e.window);
+        }
+
+        // Dropping a window onto another tiled window swaps their places in the layout
+        let dragged = match self.clients.find(&e.window) {
+            Some(i) if was_moving && !self.clients.index(i).unwrap().floating => i,
+            _ => return,
+        };
+        let target = (0..self.clients.len()).find(|&i| {
+            let client = self.clients.index(i).unwrap();
+            i != dragged
+                && !client.hidden
+                && !client.floating
+                && client.on_workspace(self.current_workspace())
+                && self.get_geometry(client.frame).contains(e.x_root, e.y_root)
+        });
+        if let Some(target) = target {
+            trace!("Swapping client {} with client {}", dragged, target);
+            self.clients.swap(dragged, target);
+        }
+
+        // Either way the dragged frame has to snap back into the layout
+        self.update_windows();
+    }
+
+    /// Gives the dragged `w` the workspace of the monitor most of its frame was dropped on, so it
+    /// stays in view there when the monitor it came from switches workspaces
+    fn drop_on_monitor(&mut self, w: Window) {
+        let (frame, workspace, sticky) = match self.clients.client(&w) {
+            Some(client) => (client.frame, client.workspace, client.sticky),
+            None => return,
+        };
+        let monitor = monitor::most_overlapping(&self.monitors, &self.get_geometry(frame));
+        let target = self.monitors[monitor].workspace;
+        if target != workspace {
+            self.clients.client_mut(&w).unwrap().workspace = target;
+            // Sticky windows are on every workspace, they just follow the monitor they are on
+            if !sticky {
+                self.set_cardinal(w, self.atoms.net_wm_desktop, target as u64);
+            }
+            info!(
+                "Moved window {} to workspace {} on monitor {}",
+                w, target, monitor
+            );
+        }
+        self.focus_monitor(monitor);
+    }
+
+    /// Whether (`x`, `y`) is close enough to the boundary between the master and stack areas to
+    /// grab it
+    fn near_divider(&self, x: i32, y: i32) -> bool {
+        const GRAB_DISTANCE: i32 = 10;
+
+        let tiled = self
+            .clients
+            .visible(self.current_workspace())
+            .filter(|c| !c.floating)
+            .count();
+        let area = self.current_area();
+        let (position, length) = match self.split_directions[self.current_workspace()] {
+            SplitDirection::Vertical => (x - area.x, area.width),
+            SplitDirection::Horizontal => (y - area.y, area.height),
+        };
+        let divider = layout::divider_position(length, self.spacing(), self.master_ratio);
+        let tiling = self.layouts[self.current_workspace()] == Layout::Tile;
+        tiling
+            && tiled > 1
+            && (position - divider).abs() <= GRAB_DISTANCE + self.config.gap as i32 / 2
+    }
+
+    /// Grabs the pointer and the server and draws the first outline for resizing `w`
+    fn start_resize_outline(&mut self, w: Window, geometry: Geometry) {
+        let display = self.display.as_ptr();
+        let gc = unsafe {
+            XGrabPointer(
+                display,
+                w,
+                False,
+                (ButtonReleaseMask | ButtonMotionMask) as c_uint,
+                GrabModeAsync,
+                GrabModeAsync,
+                0,
+                0,
+                CurrentTime,
+            );
+
+            let mut values: XGCValues = MaybeUninit::zeroed().assume_init();
+            values.function = GXxor;
+            values.subwindow_mode = IncludeInferiors;
+            values.foreground = 0xFFFFFF;
+            values.line_width = 2;
+            XCreateGC(
+                display,
+                self.root,
+                (GCFunction | GCSubwindowMode | GCForeground | GCLineWidth) as u64,
+                &mut values,
+            )
+        };
+
+        // Nothing else may draw while the outline is up, or erasing it would leave artifacts
+        let grab = ServerGrab::new(self.display);
+        self.draw_outline(gc, geometry);
+        self.resize_outline = Some(ResizeOutline { gc, geometry, grab });
+    }
+
+    fn draw_outline(&self, gc: GC, outline: Geometry) {
+        unsafe {
+            XDrawRectangle(
+                self.display.as_ptr(),
+                self.root,
+                gc,
+                outline.x,
+                outline.y,
+                outline.width.saturating_sub(1),
+                outline.height.saturating_sub(1),
+            );
+        }
+    }
+
+    fn is_decorated(&self, w: Window) -> bool {
+        self.clients
+            .client(&w)
+            .is_none_or(|client| client.decorated)
+    }
+
+    /// Moves the frame of `w` to (`x`, `y`) and resizes it to `frame_width`x`frame_height`, keeping
+    /// the client inset by its inner border
+    fn resize_frame(&self, w: Window, x: i32, y: i32, frame_width: u32, frame_height: u32) {
+        let client = match self.clients.client(&w) {
+            Some(client) => client,
+            None => return,
+        };
+        let (_, inner_border_width) = self.client_border_widths(client.decorated);
+        let frame_width = frame_width.max(2 * inner_border_width + 1);
+        let frame_height = frame_height.max(2 * inner_border_width + 1);
+
+        self.backend
+            .move_resize(client.frame, x, y, frame_width, frame_height);
+        self.backend.move_resize(
+            w,
+            inner_border_width as i32,
+            inner_border_width as i32,
+            frame_width - 2 * inner_border_width,
+            frame_height - 2 * inner_border_width,
+        );
+    }
+
+    fn get_geometry(&self, w: Window) -> Geometry {
+        let mut returned_root: Window = 0;
+        let mut x: i32 = 0;
+        let mut y: i32 = 0;
+        let mut width: u32 = 0;
+        let mut height: u32 = 0;
+        let mut border_width: u32 = 0;
+        let mut depth: u32 = 0;
+        unsafe {
+            XGetGeometry(
+                self.display.as_ptr(),
+                w,
+                &mut returned_root,
+                &mut x,
+                &mut y,
+                &mut width,
+                &mut height,
+                &mut border_width,
+                &mut depth,
+            );
+        }
+
+        Geometry {
+            x,
+            y,
+            width: width + 2 * border_width,
+            height: height + 2 * border_width,
+        }
+    }
+
+    fn on_key_pressed(&mut self, e: XKeyPressedEvent) {
+        info!("key pressed: {}", e.keycode);
+        // NumLock and CapsLock shouldn't change which binding a key press triggers
+        let state = e.state & !IGNORED_MODIFIERS;
+        // Bindings are matched against the first level of the first group, so they stay on the
+        // same keys whichever layout is active
+        let keysym = keys::normalize(unsafe {
+            XkbKeycodeToKeysym(self.display.as_ptr(), e.keycode as u8, 0, 0)
+        });
+        // The switcher has the keyboard grabbed until Mod is released
+        if self.switcher.is_some() {
+            if keysym == XK_Tab.into() {
+                self.cycle_switcher(if state & ShiftMask != 0 { -1 } else { 1 });
+            } else if keysym == XK_Escape.into() {
+                self.close_switcher(false);
+            }
+            return;
+        }
+        let mut w = 0;
+        let mut focus_state = 0;
+        unsafe {
+            XGetInputFocus(self.display.as_ptr(), &mut w, &mut focus_state);
+        }
+        trace!("current focused window: {}", w);
+        trace!("event window: {}", e.window);
+        trace!("root window: {}", self.root);
+        let matching = |b: &&KeyBinding| b.key.modifiers == state && b.key.keysym == keysym;
+        // Only bindings limited to a class need to know the focused one
+        let class = self
+            .config
+            .keys
+            .iter()
+            .filter(matching)
+            .any(|b| b.when_class.is_some())
+            .then(|| self.clients.contains(&w).then(|| self.get_class(w)))
+            .flatten()
+            .flatten();
+        if let Some(binding) = self
+            .config
+            .keys
+            .iter()
+            .filter(matching)
+            .find(|b| b.applies_to(class.as_ref()))
+        {
+            spawn_shell(&binding.command);
+        } else if state & ControlMask != 0 && keysym == XK_Q.into() {
+            match self.close_all_requested.take() {
+                Some(time) if e.time.wrapping_sub(time) <= CLOSE_ALL_CONFIRM_TIME => {
+                    self.close_workspace()
+                }
+                _ => {
+                    info!("Press Mod+Ctrl+Q again to close every window on the workspace");
+                    self.close_all_requested = Some(e.time);
+                }
+            }
+        } else if state & ShiftMask != 0 && keysym == XK_Q.into() {
+            self.force_kill(e.window);
+        } else if keysym == XK_Q.into() {
+            self.close(e.window);
+        } else if state & Mod1Mask != 0 && state & ControlMask != 0 && keysym == XK_Tab.into() {
+            let delta = if state & ShiftMask != 0 { -1 } else { 1 };
+            self.focus_within_area(delta);
+        } else if state & Mod1Mask != 0 && keysym == XK_Tab.into() {
+            trace!("clients: {:?}", self.clients);
+            let delta = if state & ShiftMask != 0 { -1 } else { 1 };
+            if self.config.window_switcher {
+                self.cycle_switcher(delta);
+            } else {
+                self.focus_relative(delta);
+            }
+        } else if state & Mod1Mask != 0 && state & ShiftMask != 0 && keysym == XK_space.into() {
+            self.toggle_floating(e.window);
+        } else if state & Mod1Mask != 0 && keysym == XK_space.into() {
+            spawn("/home/ole/dotfiles/bin/dmenu_run_history");
+        } else if state & Mod1Mask != 0 && keysym == XK_W.into() {
+            self.open_window_menu();
+        } else if state & Mod1Mask != 0 && keysym == XK_R.into() {
+            if state & ShiftMask != 0 {
+                self.reset_layout();
+            } else {
+                trace!("Updating window positions/sizes");
+                self.update_windows();
+            }
+        } else if state & Mod1Mask != 0 && state & ShiftMask != 0 && keysym == XK_C.into() {
+            self.reload_config();
+        } else if state & Mod1Mask != 0 && keysym == XK_M.into() {
+            if state & ShiftMask != 0 {
+                self.deiconify_last();
+            } else {
+                self.iconify(e.window);
+            }
+        } else if state & Mod1Mask != 0 && keysym == XK_B.into() {
+            self.toggle_borders();
+        } else if state & Mod1Mask != 0 && keysym == XK_G.into() {
+            self.gaps_enabled = !self.gaps_enabled;
+            info!(
+                "Gaps {}",
+                if self.gaps_enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            );
+            self.update_windows();
+        } else if state & Mod1Mask != 0 && state & ShiftMask != 0 && keysym == XK_D.into() {
+            self.set_showing_desktop(!self.showing_desktop);
+        } else if state & Mod1Mask != 0 && (keysym == XK_I.into() || keysym == XK_D.into()) {
+            self.adjust_nmaster(if keysym == XK_I.into() { 1 } else { -1 });
+        } else if state & Mod1Mask != 0 && state & ShiftMask != 0 && keysym == XK_T.into() {
+            self.toggle_layout();
+        } else if state & Mod1Mask != 0 && keysym == XK_T.into() {
+            self.toggle_split_direction();
+        } else if state & Mod1Mask != 0 && (keysym == XK_Home.into() || keysym == XK_End.into()) {
+            self.focus_tiled_end(keysym == XK_Home.into());
+        } else if state & Mod1Mask != 0 && (keysym == XK_comma.into() || keysym == XK_period.into())
+        {
+            self.rotate_stack(if keysym == XK_period.into() { 1 } else { -1 });
+        } else if state & Mod1Mask != 0 && keysym == XK_Z.into() {
+            self.toggle_spotlight(e.window);
+        } else if state & Mod1Mask != 0 && keysym == XK_S.into() {
+            self.toggle_sticky(e.window);
+        } else if state & Mod1Mask != 0 && keysym == XK_A.into() {
+            if let Some(client) = self.clients.client(&e.window) {
+                self.set_above(e.window, !client.above);
+            }
+        } else if state & Mod1Mask != 0 && keysym == XK_X.into() {
+            if let Some(client) = self.clients.client(&e.window) {
+                let maximize = !(client.maximized_horz && client.maximized_vert);
+                self.set_maximized(e.window, maximize, maximize);
+            }
+        } else if state & Mod1Mask != 0 && (keysym == XK_J.into() || keysym == XK_K.into()) {
+            let delta = if keysym == XK_K.into() {
+                SIZE_FACTOR_STEP
+            } else {
+                -SIZE_FACTOR_STEP
+            };
+            self.adjust_size_factor(e.window, delta);
+        } else if state & Mod1Mask != 0 && keysym == XK_Return.into() {
+            if state & ShiftMask != 0 {
+                self.promote_rotate(e.window);
+            } else {
+                self.zoom(e.window);
+            }
+        } else if state & Mod1Mask != 0 && keysym == XK_minus.into() {
+            adjust_log_level(-1);
+        } else if state & Mod1Mask != 0 && keysym == XK_equal.into() {
+            adjust_log_level(1);
+        } else if state & Mod1Mask != 0 && keysym == XK_L.into() {
+            self.toggle_layout_lock();
+        } else if state & Mod1Mask != 0 && keysym == XK_U.into() {
+            self.goto_urgent();
+        } else if state & Mod1Mask != 0 && keysym == XK_grave.into() {
+            if let Some(previous) = self.previous_workspace {
+                self.view_workspace(previous);
+            }
+        } else if state & Mod1Mask != 0
+            && (keysym == XK_bracketleft.into() || keysym == XK_bracketright.into())
+        {
+            let step = self.config.opacity_step;
+            let delta = if keysym == XK_bracketleft.into() {
+                -step
+            } else {
+                step
+            };
+            self.adjust_opacity(e.window, delta);
+        } else if let Some((dx, dy)) = arrow_direction(keysym).filter(|_| state & Mod1Mask != 0) {
+            let repeats = match self.last_nudge {
+                Some((key, modifiers, time, repeats))
+                    if key == keysym
+                        && modifiers == state
+                        && e.time.wrapping_sub(time) <= NUDGE_REPEAT_TIME =>
+                {
+                    repeats + 1
+                }
+                _ => 0,
+            };
+            self.last_nudge = Some((keysym, state, e.time, repeats));
+            let step = layout::accelerated_step(
+                self.config.keyboard_move_step,
+                self.config.keyboard_move_acceleration,
+                repeats,
+            ) as i32;
+            if state & ControlMask != 0 {
+                self.nudge_floating(e.window, 0, 0, dx * step, dy * step);
+            } else if state & ShiftMask != 0 {
+                self.nudge_floating(e.window, dx * step, dy * step, 0, 0);
+            }
+        } else if state & Mod1Mask != 0 {
+            let workspace = WORKSPACE_KEYS
+                .iter()
+                .take(self.config.workspaces)
+                .position(|&key| keysym == key.into());
+            match workspace {
+                Some(workspace) if state & ShiftMask != 0 => {
+                    self.move_to_workspace(e.window, workspace, false)
+                }
+                Some(workspace) if state & ControlMask != 0 => {
+                    self.move_to_workspace(e.window, workspace, true)
+                }
+                Some(workspace) => {
+                    // Switching acts on the monitor under the pointer
+                    if let Some((x, y)) = self.pointer_position() {
+                        self.focus_monitor(monitor::at(&self.monitors, x, y));
+                    }
+                    self.view_workspace(workspace)
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Takes `w` out of the layout, back where it was the last time it floated, or puts it back
+    /// into the layout
+    fn toggle_floating(&mut self, w: Window) {
+        let client = match self.clients.client(&w) {
+            Some(client) => client,
+            None => return,
+        };
+        let (frame, decorated) = (client.frame, client.decorated);
+        // Where a floating window is when it gets tiled covers every way it could have been moved
+        // or resized, and a maximized one is remembered at the size it will be restored to
+        let current = client
+            .restore_geometry
+            .unwrap_or_else(|| self.get_geometry(frame));
+
+        let client = self.clients.client_mut(&w).unwrap();
+        client.floating = !client.floating;
+        if !client.floating {
+            client.float_geometry = Some(current);
+            client.maximized_horz = false;
+            client.maximized_vert = false;
+            client.restore_geometry = None;
+            self.set_net_wm_state(w, self.atoms.net_wm_state_maximized_horz, false);
+            self.set_net_wm_state(w, self.atoms.net_wm_state_maximized_vert, false);
+            info!("Window {} is tiled", w);
+            self.update_windows();
+            return;
+        }
+
+        // Floating for the first time centers the window at the size it was tiled at
+        let geometry = client.float_geometry.unwrap_or_else(|| {
+            let (x, y) = self.centered(current.width, current.height);
+            Geometry { x, y, ..current }
+        });
+        let (border_width, _) = self.client_border_widths(decorated);
+        self.resize_frame(
+            w,
+            geometry.x,
+            geometry.y,
+            layout::inner_size(geometry.width, border_width),
+            layout::inner_size(geometry.height, border_width),
+        );
+        info!("Window {} is floating", w);
+        self.update_windows();
+        self.focus_window(w);
+    }
+
+    /// Focuses the master window, or the last window of the stack
+    fn focus_tiled_end(&mut self, master: bool) {
+        let tiled = self.tiled_indices();
+        let index = if master { tiled.first() } else { tiled.last() };
+        match index {
+            Some(&i) => {
+                let w = self.clients.index(i).unwrap().window;
+                self.focus_window(w);
+            }
+            None => trace!("No tiled windows on workspace {}", self.current_workspace()),
+        }
+    }
+
+    /// Positions in the client list of the windows tiled on the current workspace
+    fn tiled_indices(&self) -> Vec<usize> {
+        (0..self.clients.len())
+            .filter(|&i| {
+                let client = self.clients.index(i).unwrap();
+                client.shown() && !client.floating && client.on_workspace(self.current_workspace())
+            })
+            .collect()
+    }
+
+    /// Makes `w` the master by rotating the tiled windows, so the ones after it follow it and the
+    /// ones before it go to the end of the stack
+    fn promote_rotate(&mut self, w: Window) {
+        let tiled = self.tiled_indices();
+        let position = match self.clients.find(&w) {
+            Some(i) => match tiled.iter().position(|&t| t == i) {
+                Some(position) => position,
+                None => return,
+            },
+            None => return,
+        };
+
+        self.clients.rotate_left(&tiled, position);
+        self.update_windows();
+        self.focus_window(w);
+    }
+
+    /// Rotates the tiled windows after the master column by one place, with a positive `delta`
+    /// moving the top window of the stack to the bottom
+    fn rotate_stack(&mut self, delta: isize) {
+        let tiled = self.tiled_indices();
+        let nmaster = self.nmaster[self.current_workspace()].clamp(1, tiled.len().max(1));
+        let stack = &tiled[nmaster.min(tiled.len())..];
+        if stack.len() < 2 {
+            return;
+        }
+        let focused = self.focused_client();
+        let slot = focused
+            .and_then(|w| self.clients.find(&w))
+            .and_then(|i| stack.iter().position(|&s| s == i));
+
+        let n = if delta > 0 { 1 } else { stack.len() - 1 };
+        self.clients.rotate_left(stack, n);
+        self.update_windows();
+
+        let target = match (self.config.rotate_focus, slot) {
+            (RotateFocus::Position, Some(slot)) => {
+                self.clients.index(stack[slot]).map(|c| c.window)
+            }
+            _ => focused,
+        };
+        if let Some(w) = target {
+            self.focus_window(w);
+        }
+    }
+
+    /// Makes `w` the master window, or swaps it with the next tiled window if it already is
+    fn zoom(&mut self, w: Window) {
+        let tiled = self.tiled_indices();
+        let position = match self.clients.find(&w) {
+            Some(i) if tiled.contains(&i) => i,
+            _ => return,
+        };
+
+        let master = if position == tiled[0] {
+            match tiled.get(1) {
+                Some(&next) => {
+                    self.clients.swap(position, next);
+                    self.clients.index(position).unwrap().window
+                }
+                None => return,
+            }
+        } else {
+            self.clients.move_to_front(position);
+            w
+        };
+        self.update_windows();
+
+        let frame = *self.clients.get(&master).unwrap();
+        if self.config.raise_on_focus {
+            self.backend.raise(frame);
+            self.raise_above();
+        }
+        self.focus(master);
+    }
+
+    /// Moves a floating window by (`dx`, `dy`) and grows it by (`dw`, `dh`), keeping part of it
+    /// on screen. Tiled windows are left to the layout.
+    fn nudge_floating(&mut self, w: Window, dx: i32, dy: i32, dw: i32, dh: i32) {
+        let client = match self.clients.client(&w) {
+            Some(client) if client.floating => client,
+            _ => return,
+        };
+        let frame = client.frame;
+        let geometry = self.get_geometry(frame);
+        let (border_width, inner_border_width) = self.border_widths_of(client);
+
+        // Never shrink the client below a single step
+        let min_size = 2 * inner_border_width + self.config.keyboard_move_step.max(1);
+        let frame_width = (layout::inner_size(geometry.width, border_width) as i32 + dw)
+            .max(min_size as i32) as u32;
+        let frame_height = (layout::inner_size(geometry.height, border_width) as i32 + dh)
+            .max(min_size as i32) as u32;
+        let (x, y) = layout::keep_partially_visible(
+            geometry.x + dx,
+            geometry.y + dy,
+            frame_width + 2 * border_width,
+            frame_height + 2 * border_width,
+            self.screen_width,
+            self.screen_height,
+            self.config.keyboard_move_step,
+        );
+
+        if dw == 0 && dh == 0 {
+            self.backend.move_window(frame, x, y);
+        } else {
+            self.resize_frame(w, x, y, frame_width, frame_height);
+        }
+        self.move_transients(w, x - geometry.x, y - geometry.y);
+    }
+
+    /// Focuses the window `delta` steps away from the focused one in the client list
+    fn focus_relative(&mut self, delta: isize) {
+        let focused = self.focused_client();
+        focus::focus_relative(
+            &self.backend,
+            &self.clients,
+            self.current_workspace(),
+            focused,
+            delta,
+            self.config.raise_on_focus,
+        );
+        if self.config.raise_on_focus {
+            self.raise_above();
+        }
+    }
+
+    /// Moves the focus along the tiled windows in the same column as the focused one
+    fn focus_within_area(&mut self, delta: isize) {
+        let tiled: Vec<Window> = self
+            .clients
+            .visible(self.current_workspace())
+            .filter(|c| !c.floating)
+            .map(|c| c.window)
+            .collect();
+        let index = match self
+            .focused_client()
+            .and_then(|w| tiled.iter().position(|&t| t == w))
+        {
+            Some(index) => index,
+            None => return,
+        };
+
+        let area = layout::area(tiled.len(), self.nmaster[self.current_workspace()], index);
+        let next = focus::relative_index(area.len(), Some(index - area.start), delta);
+        self.focus_window(tiled[area.start + next]);
+    }
+
+    fn focused_client(&self) -> Option<Window> {
+        let mut w = 0;
+        let mut focus_state = 0;
+        unsafe {
+            XGetInputFocus(self.display.as_ptr(), &mut w, &mut focus_state);
+        }
+
+        self.clients.contains(&w).then_some(w)
+    }
+
+    fn iconify(&mut self, w: Window) {
+        let frame = match self.clients.client_mut(&w) {
+            Some(client) if !client.hidden => {
+                client.hidden = true;
+                // Unmapping the client ourselves generates an UnmapNotify that must not unframe it
+                client.ignore_unmaps += 1;
+                client.frame
+            }
+            _ => return,
+        };
+
+        self.backend.unmap(frame);
+        self.backend.unmap(w);
+        self.set_net_wm_state(w, self.atoms.net_wm_state_hidden, true);
+        info!("Iconified window {}", w);
+
+        self.update_windows();
+        self.focus_any();
+    }
+
+    /// Hands the focus to some window on the current workspace so the keybindings keep working
+    /// after the focused window disappears
+    fn focus_any(&mut self) {
+        let next = self
+            .clients
+            .visible(self.current_workspace())
+            .find(|c| !c.no_focus)
+            .map(|c| (c.window, c.frame));
+        match next {
+            Some((w, f)) => {
+                if self.config.raise_on_focus {
+                    self.backend.raise(f);
+                    self.raise_above();
+                }
+                self.focus(w);
+            }
+            None => self.backend.focus(PointerRoot as Window),
+        }
+    }
+
+    /// Gives the keyboard focus to `w`, unless it is a window that must never take it
+    fn focus(&self, w: Window) {
+        if self.clients.client(&w).is_some_and(|c| c.no_focus) {
+            trace!("Not focusing no-focus window {}", w);
+            return;
+        }
+        self.backend.focus(w);
+    }
+
+    /// Switches to the workspace of the first window asking for attention and focuses it
+    fn goto_urgent(&mut self) {
+        let w = match self.clients.iter().find(|c| c.urgent && !c.hidden) {
+            Some(client) => client.window,
+            None => return,
+        };
+
+        if let Some(client) = self.clients.client_mut(&w) {
+            client.urgent = false;
+        }
+        self.update_border_color(w);
+        info!("Going to urgent window {}", w);
+        self.focus_window(w);
+    }
+
+    /// Focuses and raises `w`, first switching to its workspace if no monitor shows it
+    fn focus_window(&mut self, w: Window) {
+        let (frame, workspace, desktop_hidden) = match self.clients.client(&w) {
+            Some(client) => (client.frame, client.workspace, client.desktop_hidden),
+            None => return,
+        };
+        match self.monitor_showing(workspace) {
+            Some(monitor) => self.focus_monitor(monitor),
+            None => self.view_workspace(workspace),
+        }
+        // Activating a window put away to show the desktop brings everything back
+        if desktop_hidden {
+            self.set_showing_desktop(false);
+        }
+
+        if self.config.raise_on_focus {
+            self.backend.raise(frame);
+            self.raise_above();
+        }
+        self.focus(w);
+    }
+
+    /// Shows `workspace` on the focused monitor. A workspace that is already shown on another
+    /// monitor trades places with the current one.
+    fn view_workspace(&mut self, workspace: usize) {
+        let current = self.current_workspace();
+        if workspace == current || workspace >= self.config.workspaces {
+            return;
+        }
+        let other = self.monitor_showing(workspace);
+
+        // Sticky windows stay on their monitor, whichever workspace it shows
+        for client in self.clients.iter_mut().filter(|c| c.sticky) {
+            if client.workspace == current {
+                client.workspace = workspace;
+            } else if other.is_some() && client.workspace == workspace {
+                client.workspace = current;
+            }
+        }
+        self.monitors[self.focused_monitor].workspace = workspace;
+        self.move_floating_to(workspace, self.focused_monitor);
+        if let Some(other) = other {
+            self.monitors[other].workspace = current;
+            self.move_floating_to(current, other);
+        } else {
+            for client in self.clients.iter().filter(|c| c.shown() && !c.sticky) {
+                if client.workspace == current {
+                    self.backend.unmap(client.frame);
+                } else if client.workspace == workspace {
+                    self.backend.map(client.frame);
+                }
+            }
+        }
+        self.previous_workspace = Some(current);
+        self.set_cardinal(self.root, self.atoms.net_current_desktop, workspace as u64);
+        info!("Switched to workspace {}", workspace);
+
+        self.update_windows();
+        self.focus_any();
+    }
+
+    /// Sends `w` to `workspace`, and with `follow` switches there along with it
+    fn move_to_workspace(&mut self, w: Window, workspace: usize, follow: bool) {
+        if workspace >= self.config.workspaces {
+            return;
+        }
+        let target = self.monitor_showing(workspace);
+        let client = match self.clients.client_mut(&w) {
+            Some(client) if client.workspace != workspace || client.sticky => client,
+            _ => return,
+        };
+        client.workspace = workspace;
+        // Moving a window to one workspace means it should stop following the others
+        let was_sticky = client.sticky;
+        client.sticky = false;
+        // A workspace shown on another monitor takes the window along to that monitor
+        match target {
+            Some(monitor) => self.move_floating_to(workspace, monitor),
+            None if !client.hidden => self.backend.unmap(client.frame),
+            None => {}
+        }
+        if was_sticky {
+            self.set_net_wm_state(w, self.atoms.net_wm_state_sticky, false);
+        }
+        self.set_cardinal(w, self.atoms.net_wm_desktop, workspace as u64);
+        info!("Moved window {} to workspace {}", w, workspace);
+
+        if follow {
+            self.focus_window(w);
+            return;
+        }
+        self.update_windows();
+        self.focus_any();
+    }
+
+    /// Hides every window on the current workspace but the sticky ones to show the desktop, or
+    /// brings back the windows hidden that way
+    fn set_showing_desktop(&mut self, show: bool) {
+        if show == self.showing_desktop {
+            return;
+        }
+        self.showing_desktop = show;
+
+        let current_workspace = self.current_workspace();
+        if show {
+            for client in self.clients.iter_mut() {
+                if !client.shown() || client.sticky || client.workspace != current_workspace {
+                    continue;
+                }
+                // The client stays mapped inside its unmapped frame, like on other workspaces
+                client.desktop_hidden = true;
+                self.backend.unmap(client.frame);
+            }
+            info!("Showing the desktop");
+        } else {
+            let shown: Vec<usize> = self.monitors.iter().map(|m| m.workspace).collect();
+            for client in self.clients.iter_mut().filter(|c| c.desktop_hidden) {
+                client.desktop_hidden = false;
+                // Iconified in the meantime, or left behind on a workspace that isn't shown
+                if client.shown() && shown.contains(&client.workspace) {
+                    self.backend.map(client.frame);
+                }
+            }
+            info!("Stopped showing the desktop");
+            self.raise_above();
+        }
+        self.set_cardinal(self.root, self.atoms.net_showing_desktop, show as u64);
+
+        self.update_windows();
+        self.focus_any();
+    }
+
+    fn toggle_sticky(&mut self, w: Window) {
+        if let Some(client) = self.clients.client(&w) {
+            self.set_sticky(w, !client.sticky);
+        }
+    }
+
+    fn set_sticky(&mut self, w: Window, sticky: bool) {
+        let current_workspace = self.current_workspace();
+        let off_screen = match self.clients.client(&w) {
+            Some(client) if client.sticky != sticky => {
+                self.monitor_showing(client.workspace).is_none()
+            }
+            _ => return,
+        };
+        let client = self.clients.client_mut(&w).unwrap();
+        client.sticky = sticky;
+        // Clients can ask to be sticky while on a workspace that isn't shown, which brings them
+        // into view. Otherwise they stay on the workspace of the monitor they are seen on.
+        let shown = sticky && off_screen && client.shown();
+        if off_screen {
+            client.workspace = current_workspace;
+        }
+        let workspace = client.workspace;
+        if shown {
+            self.backend.map(client.frame);
+        }
+
+        self.set_net_wm_state(w, self.atoms.net_wm_state_sticky, sticky);
+        self.set_cardinal(
+            w,
+            self.atoms.net_wm_desktop,
+            if sticky {
+                ALL_DESKTOPS
+            } else {
+                workspace as u64
+            },
+        );
+        info!(
+            "Window {} is {}",
+            w,
+            if sticky { "sticky" } else { "no longer sticky" }
+        );
+        if shown {
+            self.update_windows();
+        }
+    }
+
+    fn set_cardinal(&self, w: Window, property: Atom, value: u64) {
+        self.set_cardinals(w, property, &[value]);
+    }
+
+    fn set_cardinals(&self, w: Window, property: Atom, values: &[u64]) {
+        unsafe {
+            XChangeProperty(
+                self.display.as_ptr(),
+                w,
+                property,
+                XA_CARDINAL,
+                32,
+                PropModeReplace,
+                values.as_ptr() as *const u8,
+                values.len() as i32,
+            );
+        }
+    }
+
+    /// Sets _NET_FRAME_EXTENTS on `client` to how far its frame reaches past it on each side, for
+    /// clients that account for decorations when saving or restoring their position
+    fn update_frame_extents(&self, client: &Client) {
+        let (border_width, inner_border_width) = self.border_widths_of(client);
+        let extent = (border_width + inner_border_width) as u64;
+        // Left, right, top and bottom
+        self.set_cardinals(client.window, self.atoms.net_frame_extents, &[extent; 4]);
+    }
+
+    /// Restores the most recently framed iconified window on the current workspace, so repeated
+    /// presses cycle through all of them
+    fn deiconify_last(&mut self) {
+        let w = match self
+            .clients
+            .iter()
+            .rev()
+            .find(|c| c.hidden && !c.swallowed && c.on_workspace(self.current_workspace()))
+        {
+            Some(client) => client.window,
+            None => return,
+        };
+        self.deiconify(w);
+    }
+
+    /// Maps the iconified `w` again, and focuses it if its workspace is shown on a monitor.
+    /// Swallowed terminals stay hidden until the window that took their place is closed.
+    fn deiconify(&mut self, w: Window) {
+        let client = match self.clients.client_mut(&w) {
+            Some(client) if client.hidden && !client.swallowed => client,
+            _ => return,
+        };
+        client.hidden = false;
+        let (frame, workspace) = (client.frame, client.workspace);
+        let monitor = self.monitor_showing(workspace);
+
+        self.backend.map(w);
+        if monitor.is_some() {
+            self.backend.map(frame);
+        }
+        self.set_net_wm_state(w, self.atoms.net_wm_state_hidden, false);
+        info!("Restored window {}", w);
+
+        self.update_windows();
+        let Some(monitor) = monitor else {
+            return;
+        };
+
+        self.focus_monitor(monitor);
+        if self.config.raise_on_focus {
+            self.backend.raise(frame);
+            self.raise_above();
+        }
+        self.focus(w);
+    }
+
+    fn get_atom_list(&self, w: Window, property: Atom) -> Vec<Atom> {
+        self.get_long_property(w, property, XA_ATOM)
+    }
+
+    /// Reads a format 32 property of type `property_type`, empty if it is missing or different
+    fn get_long_property(&self, w: Window, property: Atom, property_type: Atom) -> Vec<u64> {
+        let mut actual_type = 0;
+        let mut actual_format = 0;
+        let mut num_items = 0;
+        let mut bytes_after = 0;
+        let mut data: *mut u8 = ptr::null_mut();
+
+        let status = unsafe {
+            XGetWindowProperty(
+                self.display.as_ptr(),
+                w,
+                property,
+                0,
+                1024,
+                False,
+                property_type,
+                &mut actual_type,
+                &mut actual_format,
+                &mut num_items,
+                &mut bytes_after,
+                &mut data,
+            )
+        };
+        if status != Success as i32 || data.is_null() {
+            return Vec::new();
+        }
+
+        let values = if actual_type == property_type && actual_format == 32 {
+            // Format 32 properties are returned as an array of longs
+            unsafe { std::slice::from_raw_parts(data as *const u64, num_items as usize) }.to_vec()
+        } else {
+            Vec::new()
+        };
+        unsafe {
+            XFree(data as *mut c_void);
+        }
+
+        values
+    }
+
+    fn set_atom_list(&self, w: Window, property: Atom, atoms: &[Atom]) {
+        unsafe {
+            XChangeProperty(
+                self.display.as_ptr(),
+                w,
+                property,
+                XA_ATOM,
+                32,
+                PropModeReplace,
+                atoms.as_ptr() as *const u8,
+                atoms.len() as i32,
+            );
+        }
+    }
+
+    fn set_window_list(&self, w: Window, property: Atom, windows: &[Window]) {
+        unsafe {
+            XChangeProperty(
+                self.display.as_ptr(),
+                w,
+                property,
+                XA_WINDOW,
+                32,
+                PropModeReplace,
+                windows.as_ptr() as *const u8,
+                windows.len() as i32,
+            );
+        }
+    }
+
+    fn set_above(&mut self, w: Window, above: bool) {
+        let client = match self.clients.client_mut(&w) {
+            Some(client) => client,
+            None => return,
+        };
+        client.above = above;
+
+        self.set_net_wm_state(w, self.atoms.net_wm_state_above, above);
+        self.raise_above();
+        info!(
+            "Window {} is {}",
+            w,
+            if above {
+                "always on top"
+            } else {
+                "no longer always on top"
+            }
+        );
+    }
+
+    /// Raises the always on top clients, and the focused monitor highlight over them, back over
+    /// whatever was just raised
+    fn raise_above(&self) {
+        for client in self.clients.iter().filter(|c| c.above) {
+            self.backend.raise(client.frame);
+        }
+        self.restack_transients();
+        self.highlight.raise(self.display.as_ptr());
+        self.update_stacking_list();
+    }
+
+    /// Stacks every dialog directly above the window it belongs to, so it can't get buried
+    fn restack_transients(&self) {
+        for client in self.clients.iter() {
+            let parent_frame = match client.transient_for.and_then(|p| self.clients.get(&p)) {
+                Some(&frame) => frame,
+                None => continue,
+            };
+            let changes = XWindowChanges {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+                border_width: 0,
+                sibling: parent_frame,
+                stack_mode: Above,
+            };
+            self.backend
+                .configure(client.frame, (CWSibling | CWStackMode) as u32, changes);
+        }
+    }
+
+    /// Moves the dialogs of `parent` along with it
+    fn move_transients(&self, parent: Window, dx: i32, dy: i32) {
+        if dx == 0 && dy == 0 {
+            return;
+        }
+        for client in self
+            .clients
+            .iter()
+            .filter(|c| c.transient_for == Some(parent) && c.floating)
+        {
+            let geometry = self.get_geometry(client.frame);
+            self.backend
+                .move_window(client.frame, geometry.x + dx, geometry.y + dy);
+        }
+    }
+
+    fn on_client_message(&mut self, e: XClientMessageEvent) {
+        if e.message_type == self.atoms.net_showing_desktop {
+            self.set_showing_desktop(e.data.get_long(0) != 0);
+            return;
+        }
+        if e.message_type != self.atoms.net_wm_state {
+            return;
+        }
+        let client = match self.clients.client(&e.window) {
+            Some(client) => client,
+            None => return,
+        };
+        let (above, sticky) = (client.above, client.sticky);
+        let (horz, vert) = (client.maximized_horz, client.maximized_vert);
+
+        // The action is followed by up to two properties to apply it to
+        let action = e.data.get_long(0);
+        let apply = |enabled: bool| match action {
+            NET_WM_STATE_REMOVE => false,
+            NET_WM_STATE_ADD => true,
+            _ => !enabled,
+        };
+        if ![NET_WM_STATE_REMOVE, NET_WM_STATE_ADD, NET_WM_STATE_TOGGLE].contains(&action) {
+            warn!("Unknown _NET_WM_STATE action {}", action);
+            return;
+        }
+        let properties = [e.data.get_long(1) as Atom, e.data.get_long(2) as Atom];
+
+        if properties.contains(&self.atoms.net_wm_state_above) {
+            self.set_above(e.window, apply(above));
+        }
+        if properties.contains(&self.atoms.net_wm_state_sticky) {
+            self.set_sticky(e.window, apply(sticky));
+        }
+
+        // Both directions are usually changed by the same message
+        let change_horz = properties.contains(&self.atoms.net_wm_state_maximized_horz);
+        let change_vert = properties.contains(&self.atoms.net_wm_state_maximized_vert);
+        if change_horz || change_vert {
+            self.set_maximized(
+                e.window,
+                if change_horz { apply(horz) } else { horz },
+                if change_vert { apply(vert) } else { vert },
+            );
+        }
+    }
+
+    /// Stretches a floating window across its monitor horizontally and/or vertically, restoring
+    /// the geometry it had before once it is maximized in neither direction
+    fn set_maximized(&mut self, w: Window, horz: bool, vert: bool) {
+        let client = match self.clients.client(&w) {
+            Some(client) if client.floating => client,
+            _ => return,
+        };
+        let frame = client.frame;
+        let was_maximized = client.maximized_horz || client.maximized_vert;
+        let (border_width, _) = self.border_widths_of(client);
+        let current = self.get_geometry(frame);
+        let restore_geometry = match client.restore_geometry {
+            Some(geometry) if was_maximized => geometry,
+            _ => current,
+        };
+
+        let area =
+            self.monitors[monitor::most_overlapping(&self.monitors, &restore_geometry)].geometry;
+        let mut geometry = restore_geometry;
+        if horz {
+            geometry.x = area.x;
+            geometry.width = area.width;
+        }
+        if vert {
+            geometry.y = area.y;
+            geometry.height = area.height;
+        }
+        self.resize_frame(
+            w,
+            geometry.x,
+            geometry.y,
+            layout::inner_size(geometry.width, border_width),
+            layout::inner_size(geometry.height, border_width),
+        );
+
+        let client = self.clients.client_mut(&w).unwrap();
+        client.maximized_horz = horz;
+        client.maximized_vert = vert;
+        client.restore_geometry = (horz || vert).then_some(restore_geometry);
+
+        self.set_net_wm_state(w, self.atoms.net_wm_state_maximized_horz, horz);
+        self.set_net_wm_state(w, self.atoms.net_wm_state_maximized_vert, vert);
+        self.backend.raise(frame);
+        self.raise_above();
+    }
+
+    /// Adds or removes `state` from the `_NET_WM_STATE` of `w`, leaving other states untouched
+    fn set_net_wm_state(&self, w: Window, state: Atom, enabled: bool) {
+        let mut states = self.get_atom_list(w, self.atoms.net_wm_state);
+        states.retain(|&s| s != state);
+        if enabled {
+            states.push(state);
+        }
+        self.set_atom_list(w, self.atoms.net_wm_state, &states);
+    }
+
+    fn on_mapping_notify(&mut self, mut e: XMappingEvent) {
+        unsafe {
+            XRefreshKeyboardMapping(&mut e);
+        }
+
+        if e.request == MappingKeyboard || e.request == MappingModifier {
+            info!("Keyboard mapping changed, regrabbing keys");
+            self.regrab_keys();
+        }
+    }
+
+    fn on_property_notify(&mut self, e: XPropertyEvent) {
+        if e.atom == XA_WM_HINTS {
+            self.update_urgency(e.window);
+        }
+    }
+
+    fn on_focus_in(&mut self, e: XFocusChangeEvent) {
+        // Keyboard grabs, like the ones for our own bindings, move the focus only for a moment
+        if e.mode == NotifyGrab || e.mode == NotifyUngrab {
+            return;
+        }
+
+        if let Some(client) = self.clients.client_mut(&e.window) {
+            let workspace = client.workspace;
+            if client.urgent {
+                trace!("Clearing urgency of {}", e.window);
+                client.urgent = false;
+                self.update_border_color(e.window);
+            }
+            // Focusing a window on another monitor, e.g. by clicking it, makes its workspace the
+            // current one
+            if let Some(monitor) = self.monitor_showing(workspace) {
+                self.focus_monitor(monitor);
+            }
+        }
+        // Focusing a hidden monocle window brings it into view, whether or not focus raises
+        self.raise_in_monocle(e.window);
+        if self.config.inactive_opacity < 1.0 {
+            self.update_opacity(e.window, true);
+        }
+    }
+
+    fn on_focus_out(&mut self, e: XFocusChangeEvent) {
+        if e.mode == NotifyGrab || e.mode == NotifyUngrab {
+            return;
+        }
+
+        if self.config.inactive_opacity < 1.0 {
+            self.update_opacity(e.window, false);
+        }
+    }
+
+    /// Changes the opacity of `w` by `delta`, between fully transparent and opaque
+    fn adjust_opacity(&mut self, w: Window, delta: f32) {
+        let client = match self.clients.client_mut(&w) {
+            Some(client) => client,
+            None => return,
+        };
+        client.opacity = (client.opacity + delta).clamp(0.0, 1.0);
+        info!("Opacity of window {} is now {:.2}", w, client.opacity);
+
+        let focused = self.focused_client() == Some(w);
+        self.update_opacity(w, focused);
+    }
+
+    /// Sets _NET_WM_WINDOW_OPACITY for a compositor to pick up, dimming unfocused windows to the
+    /// configured inactive opacity. Focus changes only call this with an inactive opacity
+    /// configured, since windows may set their own opacity.
+    fn update_opacity(&self, w: Window, focused: bool) {
+        let client = match self.clients.client(&w) {
+            Some(client) => client,
+            None => return,
+        };
+        let mut opacity = if focused {
+            client.opacity
+        } else {
+            client.opacity * self.config.inactive_opacity
+        };
+        if self.spotlight.as_ref().is_some_and(|s| s.window != w) {
+            opacity = opacity.min(client.opacity * SPOTLIGHT_DIM);
+        }
+
+        // Compositors differ in whether they look at the frame or the client
+        for window in [client.window, client.frame] {
+            if opacity >= 1.0 {
+                unsafe {
+                    XDeleteProperty(
+                        self.display.as_ptr(),
+                        window,
+                        self.atoms.net_wm_window_opacity,
+                    );
+                }
+            } else {
+                self.set_cardinal(
+                    window,
+                    self.atoms.net_wm_window_opacity,
+                    (opacity as f64 * u32::MAX as f64) as u64,
+                );
+            }
+        }
+    }
+
+    /// Picks up the urgency flag from the client's WM_HINTS and colors its border to match
+    fn update_urgency(&mut self, w: Window) {
+        let urgent = unsafe {
+            let hints = XGetWMHints(self.display.as_ptr(), w);
+            if hints.is_null() {
+                false
+            } else {
+                let urgent = (*hints).flags & XUrgencyHint != 0;
+                XFree(hints as *mut c_void);
+                urgent
+            }
+        };
+
+        // Urgency set while the window already has focus isn't worth drawing attention to
+        let urgent = urgent && self.focused_client() != Some(w);
+        let client = match self.clients.client_mut(&w) {
+            Some(client) => client,
+            None => return,
+        };
+        if client.urgent != urgent {
+            client.urgent = urgent;
+            self.update_border_color(w);
+        }
+    }
+
+    /// Whether `client` is tiled in the monocle layout along with other windows, which are hidden
+    /// behind whichever is on top
+    fn has_windows_behind(&self, client: &Client) -> bool {
+        let workspace = client.workspace;
+        if client.floating || self.layouts[workspace] != Layout::Monocle {
+            return false;
+        }
+        self.clients
+            .iter()
+            .filter(|c| c.shown() && !c.floating && c.workspace == workspace)
+            .count()
+            > 1
+    }
+
+    fn update_border_color(&self, w: Window) {
+        let client = match self.clients.client(&w) {
+            Some(client) => client,
+            None => return,
+        };
+        let color = if client.urgent {
+            self.colors.urgent_border
+        } else if self.has_windows_behind(client) {
+            self.colors.stacked_border
+        } else {
+            self.colors.outer_border
+        };
+        unsafe {
+            XSetWindowBorder(self.display.as_ptr(), client.frame, color);
+        }
+    }
+
+    fn on_xkb_event(&mut self, mut e: XEvent) {
+        // XKB events share one core event type and are told apart by xkb_type
+        let xkb_type = unsafe { (*(&e as *const XEvent as *const XkbAnyEvent)).xkb_type };
+        if xkb_type == XkbMapNotify {
+            unsafe {
+                XkbRefreshKeyboardMapping(&mut e as *mut XEvent as *mut XkbMapNotifyEvent);
+            }
+        }
+
+        if xkb_type == XkbNewKeyboardNotify || xkb_type == XkbMapNotify {
+            info!("Keyboard changed, regrabbing keys");
+            self.regrab_keys();
+        }
+    }
+
+    fn on_key_released(&mut self, e: XKeyReleasedEvent) {
+        info!("key released: {}", e.keycode);
+        let keysym = unsafe { XkbKeycodeToKeysym(self.display.as_ptr(), e.keycode as u8, 0, 0) };
+        if self.switcher.is_some() && MOD_KEYSYMS.iter().any(|&key| keysym == key.into()) {
+            self.close_switcher(true);
+        }
+    }
+
+    /// Opens the window switcher on the window `delta` steps away from the focused one, or moves
+    /// its selection if it is already open
+    fn cycle_switcher(&mut self, delta: isize) {
+        let display = self.display.as_ptr();
+        if let Some(switcher) = &mut self.switcher {
+            switcher.select_relative(display, delta);
+            return;
+        }
+
+        let entries: Vec<(Window, String)> = self
+            .clients
+            .visible(self.current_workspace())
+            .filter(|c| !c.no_focus)
+            .map(|c| (c.window, self.get_title(c.window)))
+            .collect();
+        if entries.is_empty() {
+            return;
+        }
+        // Without the keyboard the release of Mod would go unnoticed
+        let status = unsafe {
+            XGrabKeyboard(
+                display,
+                self.root,
+                False,
+                GrabModeAsync,
+                GrabModeAsync,
+                CurrentTime,
+            )
+        };
+        if status != GrabSuccess {
+            warn!("Failed to grab the keyboard for the window switcher");
+            self.focus_relative(delta);
+            return;
+        }
+
+        let focused = self.focused_client();
+        let current = entries.iter().position(|&(w, _)| Some(w) == focused);
+        self.switcher = Some(Switcher::open(
+            display,
+            self.root,
+            self.current_area(),
+            entries,
+            current,
+            delta,
+            self.colors.outer_border,
+        ));
+
+        // Mod may have been let go before the grab took effect
+        if self.modifiers() & Mod1Mask == 0 {
+            self.close_switcher(true);
+        }
+    }
+
+    /// Closes the window switcher, focusing the selected window if `commit` is set
+    fn close_switcher(&mut self, commit: bool) {
+        let switcher = match self.switcher.take() {
+            Some(switcher) => switcher,
+            None => return,
+        };
+        let selected = switcher.selected();
+        switcher.close(self.display.as_ptr());
+        unsafe {
+            XUngrabKeyboard(self.display.as_ptr(), CurrentTime);
+        }
+
+        // The selected window may have gone away while the switcher was open
+        let frame = match self.clients.get(&selected) {
+            Some(&frame) if commit => frame,
+            _ => return,
+        };
+        if self.config.raise_on_focus {
+            self.backend.raise(frame);
+            self.raise_above();
+        }
+        self.focus(selected);
+    }
+
+    fn on_expose(&mut self, e: XExposeEvent) {
+        // Only the last of a series of exposures needs a redraw
+        if e.count > 0 {
+            return;
+        }
+        if let Some(switcher) = self.switcher.as_ref().filter(|s| s.window() == e.window) {
+            switcher.draw(self.display.as_ptr());
+        }
+    }
+
+    /// The modifiers currently held down
+    fn modifiers(&self) -> c_uint {
+        let (mut root, mut child) = (0, 0);
+        let (mut root_x, mut root_y, mut x, mut y) = (0, 0, 0, 0);
+        let mut mask = 0;
+        unsafe {
+            XQueryPointer(
+                self.display.as_ptr(),
+                self.root,
+                &mut root,
+                &mut child,
+                &mut root_x,
+                &mut root_y,
+                &mut x,
+                &mut y,
+                &mut mask,
+            );
+        }
+        mask
+    }
+
+    fn get_title(&self, w: Window) -> String {
+        let mut name: *mut c_char = ptr::null_mut();
+        unsafe {
+            if XFetchName(self.display.as_ptr(), w, &mut name) == 0 || name.is_null() {
+                return format!("Window {}", w);
+            }
+            let title = CStr::from_ptr(name).to_string_lossy().into_owned();
+            XFree(name as *mut c_void);
+            title
+        }
+    }
+
+    fn frame(&mut self, w: Window, created_before_wm: bool) {
+        if self.is_internal(w) || self.clients.contains(&w) {
+            warn!("Refusing to frame window {}", w);
+            return;
+        }
 
         let display = self.display.as_ptr();
 
         let attributes: XWindowAttributes = unsafe {
             let mut attributes = MaybeUninit::uninit();
-            let status = XGetWindowAttributes(display, w, attributes.as_mut_ptr());
-            assert_ne!(status, 0);
+            // The window may already be gone again
+            if XGetWindowAttributes(display, w, attributes.as_mut_ptr()) == 0 {
+                warn!("Failed to get the attributes of window {}", w);
+                return;
+            }
             attributes.assume_init()
         };
 
-        if created_before_wm
-            && (attributes.override_redirect != 0 || attributes.map_state != IsViewable)
-        {
+        if created_before_wm
+            && (attributes.override_redirect != 0 || attributes.map_state != IsViewable)
+        {
+            return;
+        }
+
+        // A window opening while the desktop is shown brings the others back with it
+        if !created_before_wm {
+            self.set_showing_desktop(false);
+        }
+
+        let decorated = self.wants_decorations(w);
+        let (border_width, inner_border_width) = self.client_border_widths(decorated);
+
+        // With an inner border the frame is larger than the client, and its background shows
+        // around the client in the inner border color
+        let frame_width = attributes.width as u32 + 2 * inner_border_width;
+        let frame_height = attributes.height as u32 + 2 * inner_border_width;
+
+        // A locked layout keeps new windows out of the way of the tiled ones
+        let locked = self.layout_locked && !created_before_wm;
+        let unmapped = self.take_unmapped(w);
+        let floating = match &unmapped {
+            Some(unmapped) => unmapped.floating,
+            None => self.should_float(w) || locked,
+        };
+        let placement = if let Some(unmapped) = unmapped.as_ref().filter(|u| u.floating) {
+            Some(unmapped.position)
+        } else if floating && !created_before_wm {
+            let (width, height) = (
+                frame_width + 2 * border_width,
+                frame_height + 2 * border_width,
+            );
+            self.place_floating(width, height)
+                .or_else(|| locked.then(|| self.centered(width, height)))
+        } else {
+            None
+        };
+        let (x, y) = placement.unwrap_or_else(|| {
+            layout::requested_frame_position(
+                attributes.x,
+                attributes.y,
+                attributes.width as u32,
+                attributes.height as u32,
+                border_width,
+                inner_border_width,
+                (self.screen_width, self.screen_height),
+                self.config.offscreen_margin,
+            )
+        });
+
+        let frame = self.backend.create_frame(
+            self.root,
+            x,
+            y,
+            frame_width,
+            frame_height,
+            border_width,
+            self.colors.outer_border,
+            // The inner border is drawn by the frame's background, so it always needs one
+            if inner_border_width > 0 {
+                Some(self.colors.inner_border)
+            } else if self.config.compositor_mode {
+                None
+            } else {
+                Some(self.colors.frame_background)
+            },
+        );
+
+        self.round_corners(frame, frame_width, frame_height, border_width);
+
+        unsafe {
+            XSelectInput(
+                display,
+                frame,
+                SubstructureRedirectMask | SubstructureNotifyMask,
+            );
+            // Property changes carry urgency hints, and focus clears urgency
+            XSelectInput(display, w, PropertyChangeMask | FocusChangeMask);
+            XAddToSaveSet(display, w);
+        }
+        layout::reparent_into_frame(&self.backend, w, frame, inner_border_width);
+        if created_before_wm {
+            // Reparenting a mapped window maps it again by itself, but an adopted window that
+            // doesn't show up inside its frame would be lost until the WM exits
+            self.backend.map(w);
+        }
+        // Adopted windows stay where they were seen, on the workspace of their monitor, and new
+        // ones follow the configured policy
+        let workspace = match (&unmapped, self.config.new_window_workspace) {
+            (Some(unmapped), _) => unmapped.workspace,
+            (None, _) if created_before_wm => {
+                let geometry = Geometry {
+                    x,
+                    y,
+                    width: frame_width + 2 * border_width,
+                    height: frame_height + 2 * border_width,
+                };
+                self.monitors[monitor::most_overlapping(&self.monitors, &geometry)].workspace
+            }
+            (None, NewWindowWorkspace::Index(workspace)) => workspace,
+            (None, NewWindowWorkspace::Named(NamedWorkspace::Current)) => self.current_workspace(),
+            (None, NewWindowWorkspace::Named(NamedWorkspace::Primary)) => {
+                self.monitors[self.primary_monitor()].workspace
+            }
+        };
+        // Windows opening on a workspace that isn't shown only appear once it is viewed
+        let on_screen = self.monitor_showing(workspace).is_some();
+        if on_screen {
+            self.backend.map(frame);
+        }
+        let mut client = Client::new(w, frame, workspace);
+        client.shaped_size = Some((frame_width, frame_height, border_width));
+        client.floating = floating;
+        client.decorated = decorated;
+        client.pid = self.get_pid(w);
+        client.transient_for = self.transient_for(w);
+        client.no_focus = self.has_class(w, &self.config.no_focus_classes);
+        let pid = client.pid;
+        let terminal = if self.config.swallow
+            && !floating
+            && !created_before_wm
+            && unmapped.is_none()
+            && workspace == self.current_workspace()
+        {
+            pid.and_then(|pid| self.find_swallowing_terminal(pid))
+        } else {
+            None
+        };
+        client.swallowing = terminal;
+        self.clients.insert(client);
+        self.update_client_list();
+        if on_screen {
+            // A newly mapped frame goes on top of the stack, so put always on top windows and
+            // dialogs back above it
+            self.raise_above();
+        } else {
+            self.update_stacking_list();
+        }
+        if let Some(terminal) = terminal {
+            self.swallow(terminal, w);
+        }
+        self.set_cardinal(w, self.atoms.net_wm_desktop, workspace as u64);
+        self.update_frame_extents(self.clients.client(&w).unwrap());
+        self.update_urgency(w);
+        if self.config.inactive_opacity < 1.0 {
+            self.update_opacity(w, false);
+        }
+
+        // grab events
+        self.grab_client_keys(w);
+        self.grab_client_buttons(w);
+
+        trace!("Framed window {} [{}] of process {:?}", w, frame, pid);
+    }
+
+    /// A tiled client on the current workspace owned by one of the ancestors of process `pid`,
+    /// typically the terminal it was started from
+    fn find_swallowing_terminal(&self, pid: u32) -> Option<Window> {
+        process::ancestors(pid).into_iter().find_map(|ancestor| {
+            self.clients
+                .visible(self.current_workspace())
+                .find(|c| !c.floating && c.pid == Some(ancestor))
+                .map(|c| c.window)
+        })
+    }
+
+    /// Hides `terminal` and gives its place in the layout to the newly framed `w`
+    fn swallow(&mut self, terminal: Window, w: Window) {
+        let terminal_index = self.clients.find(&terminal).unwrap();
+        self.clients
+            .swap(terminal_index, self.clients.find(&w).unwrap());
+
+        let client = self.clients.client_mut(&terminal).unwrap();
+        client.hidden = true;
+        client.swallowed = true;
+        let frame = client.frame;
+        self.backend.unmap(frame);
+        info!("Window {} swallowed {}", w, terminal);
+    }
+
+    /// Shows the terminal swallowed by `w` again, in the place `w` had in the layout
+    fn unswallow(&mut self, w: Window) {
+        let client = self.clients.client(&w).unwrap();
+        let workspace = client.workspace;
+        let terminal = match client.swallowing {
+            Some(terminal) if self.clients.contains(&terminal) => terminal,
+            _ => return,
+        };
+
+        let terminal_index = self.clients.find(&terminal).unwrap();
+        self.clients
+            .swap(terminal_index, self.clients.find(&w).unwrap());
+
+        let on_screen = self.monitor_showing(workspace).is_some();
+        let client = self.clients.client_mut(&terminal).unwrap();
+        client.hidden = false;
+        client.swallowed = false;
+        client.workspace = workspace;
+        let frame = client.frame;
+        if on_screen {
+            self.backend.map(frame);
+        }
+        self.set_cardinal(terminal, self.atoms.net_wm_desktop, workspace as u64);
+        info!("Window {} released {}", w, terminal);
+    }
+
+    /// Picks a position on the focused monitor, or the one under the pointer, for a new floating
+    /// window with the given outer size, or `None` to keep the position the window asked for
+    fn place_floating(&mut self, width: u32, height: u32) -> Option<(i32, i32)> {
+        if self.config.spawn_at_pointer {
+            if let Some((pointer_x, pointer_y)) = self.pointer_position() {
+                let area =
+                    self.monitors[monitor::at(&self.monitors, pointer_x, pointer_y)].geometry;
+                let (x, y) = layout::clamp_to_area(
+                    pointer_x - width as i32 / 2 - area.x,
+                    pointer_y - height as i32 / 2 - area.y,
+                    width,
+                    height,
+                    area.width,
+                    area.height,
+                );
+                return Some((area.x + x, area.y + y));
+            }
+        }
+
+        if !self.config.center_new_floating {
+            return None;
+        }
+        // The layout functions place windows relative to the corner of the area
+        let area = self.current_area();
+        let (x, y) = match self.config.placement {
+            Placement::Center => return Some(self.centered(width, height)),
+            Placement::Cascade => {
+                let last = self
+                    .last_cascade
+                    .map(|(x, y)| (x - area.x, y - area.y))
+                    .filter(|&(x, y)| x >= 0 && y >= 0);
+                let (x, y) = layout::cascade_position(
+                    last,
+                    width,
+                    height,
+                    area.width,
+                    area.height,
+                    CASCADE_STEP,
+                );
+                self.last_cascade = Some((area.x + x, area.y + y));
+                (x, y)
+            }
+            Placement::Smart => {
+                let existing: Vec<Geometry> = self
+                    .clients
+                    .visible(self.current_workspace())
+                    .filter(|c| c.floating)
+                    .map(|c| {
+                        let geometry = self.get_geometry(c.frame);
+                        Geometry {
+                            x: geometry.x - area.x,
+                            y: geometry.y - area.y,
+                            ..geometry
+                        }
+                    })
+                    .collect();
+                layout::smart_position(
+                    width,
+                    height,
+                    area.width,
+                    area.height,
+                    &existing,
+                    SMART_PLACEMENT_STEP,
+                )
+            }
+        };
+        Some((area.x + x, area.y + y))
+    }
+
+    /// Where to put a window of the given outer size to center it on the focused monitor
+    fn centered(&self, width: u32, height: u32) -> (i32, i32) {
+        let area = self.current_area();
+        (
+            area.x + (area.width as i32 - width as i32) / 2,
+            area.y + (area.height as i32 - height as i32) / 2,
+        )
+    }
+
+    fn pointer_position(&self) -> Option<(i32, i32)> {
+        let mut root = 0;
+        let mut child = 0;
+        let (mut root_x, mut root_y) = (0, 0);
+        let (mut win_x, mut win_y) = (0, 0);
+        let mut mask = 0;
+        let on_screen = unsafe {
+            XQueryPointer(
+                self.display.as_ptr(),
+                self.root,
+                &mut root,
+                &mut child,
+                &mut root_x,
+                &mut root_y,
+                &mut win_x,
+                &mut win_y,
+                &mut mask,
+            )
+        };
+
+        (on_screen != 0).then_some((root_x, root_y))
+    }
+
+    /// Dialogs and transient windows float instead of being tiled
+    fn should_float(&self, w: Window) -> bool {
+        if self.transient_for(w).is_some() {
+            return true;
+        }
+
+        self.get_atom_list(w, self.atoms.net_wm_window_type)
+            .contains(&self.atoms.net_wm_window_type_dialog)
+    }
+
+    /// Asks `w` to close through WM_DELETE_WINDOW if it takes part in that protocol, and
+    /// otherwise disconnects its client
+    fn close(&self, w: Window) {
+        if !self.supports_protocol(w, self.atoms.wm_delete_window) {
+            info!("Killing window {}", w);
+            unsafe {
+                XKillClient(self.display.as_ptr(), w);
+            }
             return;
         }
 
+        info!("Asking window {} to close", w);
+        let mut data = ClientMessageData::new();
+        data.set_long(0, self.atoms.wm_delete_window as c_long);
+        data.set_long(1, CurrentTime as c_long);
+        let mut event = XEvent::from(XClientMessageEvent {
+            type_: ClientMessage,
+            serial: 0,
+            send_event: True,
+            display: self.display.as_ptr(),
+            window: w,
+            message_type: self.atoms.wm_protocols,
+            format: 32,
+            data,
+        });
         unsafe {
-            let frame = XCreateSimpleWindow(
-                display,
-                self.root,
-                attributes.x,
-                attributes.y,
-                attributes.width.try_into().unwrap(),
-                attributes.height.try_into().unwrap(),
-                self.border_width,
-                BORDER_COLOR,
-                BG_COLOR,
-            );
+            XSendEvent(self.display.as_ptr(), w, False, NoEventMask, &mut event);
+        }
+    }
 
-            XSelectInput(
-                display,
-                frame,
-                SubstructureRedirectMask | SubstructureNotifyMask,
+    fn supports_protocol(&self, w: Window, protocol: Atom) -> bool {
+        let mut protocols: *mut Atom = ptr::null_mut();
+        let mut count = 0;
+        unsafe {
+            if XGetWMProtocols(self.display.as_ptr(), w, &mut protocols, &mut count) == 0 {
+                return false;
+            }
+            let supported =
+                std::slice::from_raw_parts(protocols, count as usize).contains(&protocol);
+            XFree(protocols as *mut c_void);
+            supported
+        }
+    }
+
+    /// Closes every window on the current workspace, including the sticky ones shown on it
+    fn close_workspace(&mut self) {
+        // Collected first so the client list isn't borrowed while the windows are closed
+        let windows: Vec<Window> = self
+            .clients
+            .iter()
+            .filter(|c| c.on_workspace(self.current_workspace()))
+            .map(|c| c.window)
+            .collect();
+        info!(
+            "Closing {} windows on workspace {}",
+            windows.len(),
+            self.current_workspace()
+        );
+        for w in windows {
+            self.close(w);
+        }
+    }
+
+    /// Kills the process owning `w` for when it doesn't even react to XKillClient, provided it
+    /// runs on this machine
+    fn force_kill(&self, w: Window) {
+        let pid = match self.clients.client(&w).and_then(|c| c.pid) {
+            Some(pid) => pid,
+            None => {
+                warn!("Window {} has no _NET_WM_PID, not killing it", w);
+                return;
+            }
+        };
+        // Pids of windows from other machines mean nothing here
+        let machine = self.get_client_machine(w);
+        let hostname = process::hostname();
+        if machine.is_none() || machine != hostname {
+            warn!(
+                "Window {} runs on {:?} rather than {:?}, not killing pid {}",
+                w, machine, hostname, pid
             );
-            XAddToSaveSet(display, w);
-            XReparentWindow(display, w, frame, 0, 0);
-            XMapWindow(display, frame);
-            self.clients.insert(w, frame);
+            return;
+        }
 
-            // grab events
-            self.grab_key(Mod1Mask, XK_Q, w);
-            self.grab_key(Mod1Mask, XK_Tab, w);
-            self.grab_button(Mod1Mask, Button1, w);
+        match process::force_kill(pid) {
+            Ok(()) => info!("Killed pid {} owning window {}", pid, w),
+            Err(err) => error!("Failed to kill pid {} owning window {}: {}", pid, w, err),
+        }
+    }
 
-            trace!("Framed window {} [{}]", w, frame);
+    fn get_client_machine(&self, w: Window) -> Option<String> {
+        unsafe {
+            let mut property = MaybeUninit::<XTextProperty>::uninit();
+            if XGetWMClientMachine(self.display.as_ptr(), w, property.as_mut_ptr()) == 0 {
+                return None;
+            }
+            let property = property.assume_init();
+            if property.value.is_null() {
+                return None;
+            }
+            let bytes = std::slice::from_raw_parts(property.value, property.nitems as usize);
+            let machine = String::from_utf8_lossy(bytes).into_owned();
+            XFree(property.value as *mut c_void);
+            Some(machine)
         }
     }
 
-    fn grab_button(&self, modifiers: c_uint, button: c_uint, w: Window) {
+    /// Whether the WM_CLASS instance or class name of `w` is one of `classes`
+    fn has_class(&self, w: Window, classes: &[String]) -> bool {
+        !classes.is_empty()
+            && self.get_class(w).is_some_and(|(instance, class)| {
+                classes.iter().any(|c| *c == instance || *c == class)
+            })
+    }
+
+    /// The instance and class names from WM_CLASS
+    fn get_class(&self, w: Window) -> Option<(String, String)> {
         unsafe {
-            XGrabButton(
+            let mut hint: XClassHint = MaybeUninit::zeroed().assume_init();
+            if XGetClassHint(self.display.as_ptr(), w, &mut hint) == 0 {
+                return None;
+            }
+            let take = |name: *mut c_char| {
+                if name.is_null() {
+                    return String::new();
+                }
+                let string = CStr::from_ptr(name).to_string_lossy().into_owned();
+                XFree(name as *mut c_void);
+                string
+            };
+            Some((take(hint.res_name), take(hint.res_class)))
+        }
+    }
+
+    fn transient_for(&self, w: Window) -> Option<Window> {
+        let mut transient_for = 0;
+        if unsafe { XGetTransientForHint(self.display.as_ptr(), w, &mut transient_for) } != 0 {
+            Some(transient_for)
+        } else {
+            None
+        }
+    }
+
+    /// The process that owns `w`, as reported through _NET_WM_PID
+    fn get_pid(&self, w: Window) -> Option<u32> {
+        self.get_long_property(w, self.atoms.net_wm_pid, XA_CARDINAL)
+            .first()
+            .map(|&pid| pid as u32)
+    }
+
+    fn round_corners(&self, frame: Window, width: u32, height: u32, border_width: u32) {
+        if self.config.corner_radius > 0 && self.shape_supported {
+            shape::round_corners(
                 self.display.as_ptr(),
-                button,
-                modifiers,
-                w,
-                0,
-                (ButtonPressMask | ButtonReleaseMask | ButtonMotionMask)
-                    .try_into()
-                    .unwrap(),
-                GrabModeAsync,
-                GrabModeAsync,
-                0,
-                0,
+                frame,
+                width,
+                height,
+                border_width,
+                self.config.corner_radius,
             );
         }
     }
 
-    fn grab_key(&self, modifiers: c_uint, key_code: c_uint, w: Window) {
-        unsafe {
-            XGrabKey(
-                self.display.as_ptr(),
-                XKeysymToKeycode(self.display.as_ptr(), key_code.into()).into(),
-                modifiers,
-                w,
-                0,
-                GrabModeAsync,
-                GrabModeAsync,
+    /// Whether `w` is one of the window manager's own windows, which are never framed or focused
+    fn is_internal(&self, w: Window) -> bool {
+        w == self.root || self.highlight.contains(w) || self.clients.iter().any(|c| c.frame == w)
+    }
+
+    /// False if the window asked not to be decorated through _MOTIF_WM_HINTS
+    fn wants_decorations(&self, w: Window) -> bool {
+        // The hints are flags, functions, decorations, input mode and status, and the
+        // decorations field is only meaningful if its flag is set
+        const MWM_HINTS_DECORATIONS: u64 = 1 << 1;
+
+        let hints = self.get_long_property(w, self.atoms.motif_wm_hints, self.atoms.motif_wm_hints);
+        match hints[..] {
+            [flags, _, decorations, ..] if flags & MWM_HINTS_DECORATIONS != 0 => decorations != 0,
+            _ => true,
+        }
+    }
+
+    fn grab_root_keys(&self) {
+        self.grab_key(Mod1Mask, XK_space, self.root);
+        self.grab_key(Mod1Mask, XK_R, self.root);
+        self.grab_key(Mod1Mask | ShiftMask, XK_R, self.root);
+        self.grab_key(Mod1Mask | ShiftMask, XK_C, self.root);
+        self.grab_key(Mod1Mask | ShiftMask, XK_M, self.root);
+        self.grab_key(Mod1Mask, XK_B, self.root);
+        self.grab_key(Mod1Mask, XK_G, self.root);
+        self.grab_key(Mod1Mask, XK_I, self.root);
+        self.grab_key(Mod1Mask | ControlMask, XK_Q, self.root);
+        self.grab_key(Mod1Mask, XK_Home, self.root);
+        self.grab_key(Mod1Mask, XK_comma, self.root);
+        self.grab_key(Mod1Mask, XK_period, self.root);
+        self.grab_key(Mod1Mask, XK_End, self.root);
+        self.grab_key(Mod1Mask, XK_D, self.root);
+        self.grab_key(Mod1Mask | ShiftMask, XK_D, self.root);
+        self.grab_key(Mod1Mask, XK_L, self.root);
+        self.grab_key(Mod1Mask, XK_U, self.root);
+        self.grab_key(Mod1Mask, XK_grave, self.root);
+        self.grab_key(Mod1Mask, XK_minus, self.root);
+        self.grab_key(Mod1Mask, XK_equal, self.root);
+        self.grab_key(Mod1Mask, XK_W, self.root);
+        self.grab_key(Mod1Mask, XK_T, self.root);
+        self.grab_key(Mod1Mask | ShiftMask, XK_T, self.root);
+        for &key in WORKSPACE_KEYS.iter().take(self.config.workspaces) {
+            self.grab_key(Mod1Mask, key, self.root);
+        }
+        // Bindings limited to a class are only grabbed on the windows of that class, so other
+        // windows keep the key
+        for binding in self.config.keys.iter().filter(|b| b.when_class.is_none()) {
+            self.grab_key(
+                binding.key.modifiers,
+                binding.key.keysym as c_uint,
+                self.root,
             );
         }
     }
 
-    fn on_map_request(&mut self, e: XMapRequestEvent) {
-        self.frame(e.window, false);
+    fn grab_client_keys(&self, w: Window) {
+        self.grab_key(Mod1Mask, XK_Q, w);
+        self.grab_key(Mod1Mask, XK_J, w);
+        self.grab_key(Mod1Mask, XK_K, w);
+        self.grab_key(Mod1Mask | ShiftMask, XK_Q, w);
+        self.grab_key(Mod1Mask, XK_Tab, w);
+        self.grab_key(Mod1Mask | ShiftMask, XK_Tab, w);
+        self.grab_key(Mod1Mask | ControlMask, XK_Tab, w);
+        self.grab_key(Mod1Mask | ControlMask | ShiftMask, XK_Tab, w);
+        self.grab_key(Mod1Mask, XK_M, w);
+        self.grab_key(Mod1Mask, XK_S, w);
+        self.grab_key(Mod1Mask, XK_A, w);
+        self.grab_key(Mod1Mask, XK_X, w);
+        self.grab_key(Mod1Mask, XK_Z, w);
+        self.grab_key(Mod1Mask, XK_bracketleft, w);
+        self.grab_key(Mod1Mask, XK_bracketright, w);
+        self.grab_key(Mod1Mask, XK_Return, w);
+        self.grab_key(Mod1Mask | ShiftMask, XK_space, w);
+        self.grab_key(Mod1Mask | ShiftMask, XK_Return, w);
+        for key in [XK_Left, XK_Right, XK_Up, XK_Down] {
+            self.grab_key(Mod1Mask | ShiftMask, key, w);
+            self.grab_key(Mod1Mask | ControlMask, key, w);
+        }
+        for &key in WORKSPACE_KEYS.iter().take(self.config.workspaces) {
+            self.grab_key(Mod1Mask | ShiftMask, key, w);
+            self.grab_key(Mod1Mask | ControlMask, key, w);
+        }
+        let class = self.get_class(w);
+        for binding in self
+            .config
+            .keys
+            .iter()
+            .filter(|b| b.when_class.is_some() && b.applies_to(class.as_ref()))
+        {
+            self.grab_key(binding.key.modifiers, binding.key.keysym as c_uint, w);
+        }
+    }
 
+    /// Drops all key grabs and grabs the keybindings again, resolving their keysyms against the
+    /// current keyboard mapping
+    fn regrab_keys(&self) {
         unsafe {
-            XMapWindow(self.display.as_ptr(), e.window);
+            XUngrabKey(self.display.as_ptr(), AnyKey, AnyModifier, self.root);
+        }
+        self.grab_root_keys();
+
+        for client in self.clients.iter() {
+            unsafe {
+                XUngrabKey(self.display.as_ptr(), AnyKey, AnyModifier, client.window);
+            }
+            self.grab_client_keys(client.window);
+        }
+    }
+
+    fn grab_client_buttons(&self, w: Window) {
+        for binding in &self.config.buttons {
+            // Only moving and resizing follow the pointer after the press
+            let drags = matches!(binding.action, ButtonAction::Move | ButtonAction::Resize);
+            self.grab_button(binding.button.modifiers, binding.button.button, w, drags);
+        }
+    }
+
+    fn grab_button(&self, modifiers: c_uint, button: c_uint, w: Window, motion: bool) {
+        let mut event_mask = ButtonPressMask | ButtonReleaseMask;
+        if motion {
+            event_mask |= ButtonMotionMask;
+        }
+        for extra in LOCK_MODIFIER_COMBINATIONS {
+            unsafe {
+                XGrabButton(
+                    self.display.as_ptr(),
+                    button,
+                    modifiers | extra,
+                    w,
+                    0,
+                    event_mask.try_into().unwrap(),
+                    GrabModeAsync,
+                    GrabModeAsync,
+                    0,
+                    0,
+                );
+            }
+        }
+    }
+
+    fn grab_key(&self, modifiers: c_uint, key_code: c_uint, w: Window) {
+        // Grabs only match the exact modifiers, so also grab the binding with the lock keys active
+        for extra in LOCK_MODIFIER_COMBINATIONS {
+            unsafe {
+                XGrabKey(
+                    self.display.as_ptr(),
+                    XKeysymToKeycode(self.display.as_ptr(), key_code.into()).into(),
+                    modifiers | extra,
+                    w,
+                    0,
+                    GrabModeAsync,
+                    GrabModeAsync,
+                );
+            }
+        }
+    }
+
+    fn on_map_request(&mut self, e: XMapRequestEvent) {
+        let known = self.clients.contains(&e.window);
+        if known {
+            // An iconified window mapping itself again, like when a taskbar activates it, asks to
+            // be restored
+            self.deiconify(e.window);
+        } else if self.has_class(e.window, &self.config.unmanaged_classes) {
+            // Their unmaps and configure requests are passed through like those of any unknown
+            // window
+            self.backend.map(e.window);
+            trace!("Mapped unmanaged window {}", e.window);
+        } else {
+            self.frame(e.window, false);
+            self.backend.map(e.window);
             trace!("Mapped window {}", e.window);
         }
 
-        self.update_windows();
+        // When many windows map at once, like on session restore, tiling after the last one is
+        // enough
+        if self.next_event_type() != Some(MapRequest) {
+            self.update_windows();
+        }
+
+        if !known && self.wants_focus_on_map(e.window) {
+            let frame = *self.clients.get(&e.window).unwrap();
+            self.backend.raise(frame);
+            self.raise_above();
+            self.focus(e.window);
+        }
+    }
+
+    /// Whether the newly mapped `w` should take the focus from the focused window
+    fn wants_focus_on_map(&self, w: Window) -> bool {
+        let client = match self.clients.client(&w) {
+            Some(client) => client,
+            None => return false,
+        };
+        // A user time of 0 means the window was not opened by the user and shouldn't be focused
+        let user_time = self.get_long_property(w, self.atoms.net_wm_user_time, XA_CARDINAL);
+        self.config.focus_new_windows
+            && client.on_workspace(self.current_workspace())
+            && !client.hidden
+            && user_time.first() != Some(&0)
+    }
+
+    /// The type of the next event that has already been read from the connection, without
+    /// removing it from the queue
+    fn next_event_type(&self) -> Option<c_int> {
+        unsafe {
+            if XEventsQueued(self.display.as_ptr(), QUEUED_ALREADY) == 0 {
+                return None;
+            }
+            let mut e = MaybeUninit::uninit();
+            XPeekEvent(self.display.as_ptr(), e.as_mut_ptr());
+            Some(e.assume_init().get_type())
+        }
     }
 
     fn unframe(&mut self, w: Window) {
         let frame = *self.clients.get(&w).unwrap();
+        let (border_width, inner_border_width) =
+            self.border_widths_of(self.clients.client(&w).unwrap());
+        self.unswallow(w);
+
+        // The client goes back to the root where it was seen inside its frame, so windows don't
+        // pile up in the corner when the window manager exits
+        let geometry = self.get_geometry(frame);
 
+        self.backend.unmap(frame);
+        layout::reparent_out_of_frame(
+            &self.backend,
+            w,
+            self.root,
+            (geometry.x, geometry.y),
+            border_width,
+            inner_border_width,
+        );
         unsafe {
-            XUnmapWindow(self.display.as_ptr(), frame);
-            XReparentWindow(self.display.as_ptr(), w, self.root, 0, 0);
             XRemoveFromSaveSet(self.display.as_ptr(), w);
-            XDestroyWindow(self.display.as_ptr(), frame);
-            self.clients.remove(&w);
+        }
+        self.forget_client(w);
+
+        trace!("Unframed window {} [{}]", w, frame);
+    }
+
+    fn remember_unmapped(&mut self, w: Window) {
+        let client = self.clients.client(&w).unwrap();
+        let (workspace, floating) = (client.workspace, client.floating);
+        let geometry = self.get_geometry(client.frame);
+        self.recently_unmapped
+            .retain(|u| u.window != w && u.time.elapsed() < REMEMBER_UNMAPPED_FOR);
+        self.recently_unmapped.push(Unmapped {
+            window: w,
+            workspace,
+            floating,
+            position: (geometry.x, geometry.y),
+            time: Instant::now(),
+        });
+    }
+
+    /// Where `w` was before it unmapped itself, if that was recent enough to put it back there
+    fn take_unmapped(&mut self, w: Window) -> Option<Unmapped> {
+        self.recently_unmapped
+            .retain(|u| u.time.elapsed() < REMEMBER_UNMAPPED_FOR);
+        let i = self.recently_unmapped.iter().position(|u| u.window == w)?;
+        Some(self.recently_unmapped.remove(i))
+    }
 
-            trace!("Unframed window {} [{}]", w, frame);
+    /// Destroys the frame of `w` and drops it from the client list, once the client itself is
+    /// back on the root or gone
+    fn forget_client(&mut self, w: Window) {
+        let frame = *self.clients.get(&w).unwrap();
+        self.backend.destroy(frame);
+        self.clients.remove(&w);
+        self.update_client_list();
+        self.update_stacking_list();
+        if self.spotlight.as_ref().is_some_and(|s| s.window == w) {
+            self.end_spotlight();
         }
     }
 
     fn on_unmap_notify(&mut self, e: XUnmapEvent) {
-        if e.event != self.root && self.clients.contains(&e.window) {
-            self.unframe(e.window);
+        if e.event == self.root {
+            return;
+        }
+
+        match self.clients.client_mut(&e.window) {
+            Some(client) if client.ignore_unmaps > 0 => {
+                client.ignore_unmaps -= 1;
+                trace!("Ignored unmap of window {}", e.window);
+            }
+            Some(_) => {
+                self.remember_unmapped(e.window);
+                self.unframe(e.window);
+                if !self.layout_locked {
+                    self.update_windows();
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Grows or shrinks the share of the stack taken by the tiled window `w`
+    fn adjust_size_factor(&mut self, w: Window, delta: f32) {
+        let client = match self.clients.client_mut(&w) {
+            Some(client) if !client.floating => client,
+            _ => return,
+        };
+        client.size_factor = (client.size_factor + delta).clamp(MIN_SIZE_FACTOR, MAX_SIZE_FACTOR);
+        trace!("Size factor of {} is now {}", w, client.size_factor);
+        self.update_windows();
+    }
+
+    /// Undoes any resizing of the tiled windows, going back to the configured master ratio
+    fn reset_layout(&mut self) {
+        self.master_ratio = self.config.master_ratio;
+        for client in self.clients.iter_mut() {
+            client.size_factor = 1.0;
+        }
+        info!("Reset the master ratio to {:.2}", self.master_ratio);
+        self.update_windows();
+    }
+
+    /// Reads the config file again and applies it to the bindings and the existing frames.
+    /// The number of workspaces is kept, since windows and key grabs depend on it.
+    fn reload_config(&mut self) {
+        let resources = Resources::load(self.display.as_ptr());
+        let mut config = match Config::reload(|name| resources.get(name)) {
+            Some(config) => config,
+            None => {
+                error!("Keeping the current config");
+                return;
+            }
+        };
+        if config.workspaces != self.config.workspaces {
+            warn!(
+                "Changing the number of workspaces from {} to {} needs a restart",
+                self.config.workspaces, config.workspaces
+            );
+            config.workspaces = self.config.workspaces;
+            config.validate();
+        }
+        // Keep the current ratio unless the configured one changed
+        if config.master_ratio != self.config.master_ratio {
+            self.master_ratio = config.master_ratio;
+        }
+        let old_inner_border_width = self.config.inner_border_width;
+        if let Some(color) = config
+            .wallpaper
+            .as_ref()
+            .filter(|&c| Some(c) != self.config.wallpaper.as_ref())
+        {
+            wallpaper::set(self.display.as_ptr(), color);
+        }
+        self.config = config;
+        // The frames are given the new colors below, before the old ones are freed
+        let old_colors = std::mem::replace(
+            &mut self.colors,
+            Colors::alloc(self.display.as_ptr(), &self.config),
+        );
+
+        self.regrab_keys();
+        for client in self.clients.iter() {
+            unsafe {
+                XUngrabButton(
+                    self.display.as_ptr(),
+                    AnyButton as c_uint,
+                    AnyModifier,
+                    client.window,
+                );
+            }
+            self.grab_client_buttons(client.window);
+        }
+
+        for client in self.clients.iter() {
+            let (border_width, inner_border_width) = self.border_widths_of(client);
+            let display = self.display.as_ptr();
+            unsafe {
+                XSetWindowBorderWidth(display, client.frame, border_width);
+                // Same choice of background as when the frame was created
+                if inner_border_width > 0 {
+                    XSetWindowBackground(display, client.frame, self.colors.inner_border);
+                    XClearWindow(display, client.frame);
+                } else if !self.config.compositor_mode {
+                    XSetWindowBackground(display, client.frame, self.colors.frame_background);
+                }
+            }
+            self.update_border_color(client.window);
+            self.update_frame_extents(client);
+
+            let geometry = self.get_geometry(client.frame);
+            self.round_corners(
+                client.frame,
+                geometry.width - 2 * border_width,
+                geometry.height - 2 * border_width,
+                border_width,
+            );
+        }
+        self.highlight
+            .set_color(self.display.as_ptr(), self.colors.focused_monitor_border);
+        self.update_highlight();
+        old_colors.free(self.display.as_ptr());
+
+        // Tiled clients are inset again by the retile, floating ones keep their size and get a
+        // frame to match
+        if self.config.inner_border_width != old_inner_border_width {
+            let floating: Vec<(Window, Window, bool)> = self
+                .clients
+                .iter()
+                .filter(|c| c.floating)
+                .map(|c| (c.window, c.frame, c.decorated))
+                .collect();
+            for (w, frame, decorated) in floating {
+                let (_, inner_border_width) = self.client_border_widths(decorated);
+                let frame_geometry = self.get_geometry(frame);
+                let client_geometry = self.get_geometry(w);
+                self.resize_frame(
+                    w,
+                    frame_geometry.x,
+                    frame_geometry.y,
+                    client_geometry.width + 2 * inner_border_width,
+                    client_geometry.height + 2 * inner_border_width,
+                );
+            }
+        }
+
+        info!("Reloaded the config");
+        self.update_windows();
+    }
+
+    /// Enlarges `w` in the middle of the screen and dims the other windows, or puts everything
+    /// back if a window is already spotlit. Tiled windows float while they are spotlit.
+    fn toggle_spotlight(&mut self, w: Window) {
+        if self.spotlight.is_some() {
+            self.end_spotlight();
+            self.update_windows();
+            return;
+        }
+
+        let client = match self.clients.client_mut(&w) {
+            Some(client) => client,
+            None => return,
+        };
+        let floating = client.floating;
+        client.floating = true;
+        let (frame, decorated) = (client.frame, client.decorated);
+        self.spotlight = Some(Spotlight {
+            window: w,
+            geometry: self.get_geometry(frame),
+            floating,
+        });
+
+        let (border_width, _) = self.client_border_widths(decorated);
+        let area = self.current_area();
+        let width = (area.width as f32 * SPOTLIGHT_SIZE) as u32;
+        let height = (area.height as f32 * SPOTLIGHT_SIZE) as u32;
+        let (x, y) = self.centered(width, height);
+        self.resize_frame(
+            w,
+            x,
+            y,
+            layout::inner_size(width, border_width),
+            layout::inner_size(height, border_width),
+        );
+        self.backend.raise(frame);
+        self.raise_above();
+        self.update_opacities();
+        info!("Spotlit window {}", w);
+
+        self.update_windows();
+    }
+
+    /// Gives the spotlit window its old geometry back and undims the others
+    fn end_spotlight(&mut self) {
+        let spotlight = match self.spotlight.take() {
+            Some(spotlight) => spotlight,
+            None => return,
+        };
+        if let Some(client) = self.clients.client_mut(&spotlight.window) {
+            client.floating = spotlight.floating;
+            let decorated = client.decorated;
+            // Tiled windows get their place back from the next retile
+            if spotlight.floating {
+                let (border_width, _) = self.client_border_widths(decorated);
+                let geometry = spotlight.geometry;
+                self.resize_frame(
+                    spotlight.window,
+                    geometry.x,
+                    geometry.y,
+                    layout::inner_size(geometry.width, border_width),
+                    layout::inner_size(geometry.height, border_width),
+                );
+            }
+        }
+        self.update_opacities();
+        info!("Ended the spotlight on window {}", spotlight.window);
+    }
+
+    fn update_opacities(&self) {
+        let focused = self.focused_client();
+        for client in self.clients.iter() {
+            self.update_opacity(client.window, focused == Some(client.window));
+        }
+    }
+
+    /// Changes how many windows share the master column on the current workspace, keeping at
+    /// least one and no more than there are tiled windows
+    fn adjust_nmaster(&mut self, delta: isize) {
+        let tiled = self
+            .clients
+            .visible(self.current_workspace())
+            .filter(|c| !c.floating)
+            .count();
+        let workspace = self.current_workspace();
+        let nmaster = &mut self.nmaster[workspace];
+        *nmaster = (*nmaster as isize + delta).clamp(1, tiled.max(1) as isize) as usize;
+        info!("{} windows in the master column", nmaster);
+        self.update_windows();
+    }
+
+    /// Switches the current workspace between the master on the left and the master on top
+    fn toggle_split_direction(&mut self) {
+        let workspace = self.current_workspace();
+        let direction = &mut self.split_directions[workspace];
+        *direction = match direction {
+            SplitDirection::Vertical => SplitDirection::Horizontal,
+            SplitDirection::Horizontal => SplitDirection::Vertical,
+        };
+        info!("Workspace {} is split {:?}", workspace, direction);
+        self.update_windows();
+    }
+
+    /// Switches the current workspace back to the layout it had before the last toggle, or to
+    /// monocle the first time
+    fn toggle_layout(&mut self) {
+        let workspace = self.current_workspace();
+        let current = self.layouts[workspace];
+        let next = match self.previous_layouts[workspace] {
+            Some(previous) => previous,
+            None if current == Layout::Monocle => Layout::Tile,
+            None => Layout::Monocle,
+        };
+        self.previous_layouts[workspace] = Some(current);
+        self.layouts[workspace] = next;
+        info!("Workspace {} is in the {:?} layout", workspace, next);
+        self.update_windows();
+    }
+
+    fn toggle_layout_lock(&mut self) {
+        self.layout_locked = !self.layout_locked;
+        info!(
+            "Layout {}",
+            if self.layout_locked {
+                "locked"
+            } else {
+                "unlocked"
+            }
+        );
+        // Fill any holes left while it was locked
+        if !self.layout_locked {
+            self.update_windows();
         }
     }
 
@@ -482,30 +3975,119 @@ impl WindowManager {
             stack_mode: e.detail,
         };
 
-        if let Some(&frame) = self.clients.get(&e.window) {
-            unsafe {
-                XConfigureWindow(
-                    self.display.as_ptr(),
-                    frame,
-                    e.value_mask.try_into().unwrap(),
-                    &mut changes,
-                );
+        let client = match self.clients.client(&e.window) {
+            Some(client) => client,
+            None => {
+                // Windows we don't manage get what they ask for
+                self.backend
+                    .configure(e.window, e.value_mask.try_into().unwrap(), changes);
+                trace!("Configured unmanaged window {}", e.window);
+                return;
+            }
+        };
+
+        // The requested sibling is a client, but the frames are what is actually stacked
+        // under the root, so restack relative to the sibling's frame instead.
+        let mut value_mask = e.value_mask;
+        if value_mask & CWSibling as u64 != 0 {
+            match self.clients.get(&e.above) {
+                Some(&sibling_frame) => changes.sibling = sibling_frame,
+                None => value_mask &= !(CWSibling as u64),
             }
         }
 
+        let (border_width, inner_border_width) = self.border_widths_of(client);
+        layout::configure_framed(
+            &self.backend,
+            e.window,
+            client.frame,
+            value_mask.try_into().unwrap(),
+            changes,
+            border_width,
+            inner_border_width,
+        );
+        if value_mask & CWStackMode as u64 != 0 {
+            self.update_stacking_list();
+        }
+        trace!("Configured window {}", e.window);
+    }
+
+    /// Tells the client `w` where it now is on screen after its frame was configured as in
+    /// `frame`. Moving the frame doesn't move the client relative to its parent, so without this
+    /// clients like pseudo-transparent terminals would think they are still in the top left corner.
+    fn send_configure_notify(&self, w: Window, decorated: bool, frame: &XConfigureEvent) {
+        let (_, inner_border_width) = self.client_border_widths(decorated);
+        let (x, y) = layout::client_position(
+            frame.x,
+            frame.y,
+            frame.border_width as u32,
+            inner_border_width,
+        );
+        let inner = 2 * inner_border_width as i32;
+        let mut event = XEvent::from(XConfigureEvent {
+            type_: ConfigureNotify,
+            serial: 0,
+            send_event: True,
+            display: self.display.as_ptr(),
+            event: w,
+            window: w,
+            x,
+            y,
+            width: (frame.width - inner).max(1),
+            height: (frame.height - inner).max(1),
+            border_width: 0,
+            above: 0,
+            override_redirect: False,
+        });
         unsafe {
-            XConfigureWindow(
+            XSendEvent(
                 self.display.as_ptr(),
-                e.window,
-                e.value_mask.try_into().unwrap(),
-                &mut changes,
+                w,
+                False,
+                StructureNotifyMask,
+                &mut event,
             );
-
-            trace!("Configured window {}", e.window);
         }
     }
 
-    fn on_configure_notify(&mut self, _e: XConfigureEvent) {}
+    fn on_configure_notify(&mut self, e: XConfigureEvent) {
+        // The shape has to follow the frame's size, but not its position
+        let size = (e.width as u32, e.height as u32, e.border_width as u32);
+        let frame_of = self
+            .clients
+            .iter_mut()
+            .find(|c| c.frame == e.window)
+            .map(|client| {
+                let resized = client.shaped_size.replace(size) != Some(size);
+                (client.window, client.decorated, resized)
+            });
+        if let Some((w, decorated, resized)) = frame_of {
+            if resized {
+                self.round_corners(e.window, size.0, size.1, size.2);
+            }
+            self.send_configure_notify(w, decorated, &e);
+            return;
+        }
+
+        // Otherwise only the root window being resized matters, e.g. when the screen size changes
+        if e.window != self.root {
+            return;
+        }
+        let (width, height) = (e.width as u32, e.height as u32);
+        if (width, height) == (self.screen_width, self.screen_height) {
+            return;
+        }
+
+        info!("Screen resized to {}x{}", width, height);
+        self.screen_width = width;
+        self.screen_height = height;
+        self.update_monitors();
+        // The wallpaper pixmap was made for the old size
+        if let Some(color) = &self.config.wallpaper {
+            wallpaper::set(self.display.as_ptr(), color);
+        }
+        self.update_windows();
+    }
 
     fn on_create_notify(&mut self, e: XCreateWindowEvent) {
         trace!("Window {} created", e.window);
@@ -513,15 +4095,59 @@ impl WindowManager {
 
     fn on_destroy_notify(&mut self, e: XDestroyWindowEvent) {
         trace!("Window {} destroyed", e.window);
+        // The id may be reused by an unrelated window
+        self.recently_unmapped.retain(|u| u.window != e.window);
+
+        // Normally the unmap before the destruction has unframed the window already, but not if
+        // that unmap was ignored or the window was never mapped, as with iconified windows
+        if !self.clients.contains(&e.window) {
+            return;
+        }
+        self.unswallow(e.window);
+        self.forget_client(e.window);
+        trace!("Dropped destroyed window {}", e.window);
+        if !self.layout_locked {
+            self.update_windows();
+        }
     }
 
     fn on_reparent_notify(&mut self, e: XReparentEvent) {
         trace!("Window {} reparented", e.window);
     }
 
-    extern "C" fn on_x_error(_: *mut Display, e: *mut XErrorEvent) -> i32 {
+    extern "C" fn on_x_error(display: *mut Display, e: *mut XErrorEvent) -> i32 {
         let e = unsafe { &*e };
-        error!("X Error: {:?}", e);
+
+        let mut text = [0 as c_char; 256];
+        unsafe {
+            XGetErrorText(
+                display,
+                e.error_code.into(),
+                text.as_mut_ptr(),
+                text.len() as i32,
+            );
+        }
+        let text = unsafe { CStr::from_ptr(text.as_ptr()) }.to_string_lossy();
+
+        // Xlib's error database knows the names of the core protocol requests
+        let mut request = [0 as c_char; 256];
+        let opcode = CString::new(e.request_code.to_string()).unwrap();
+        unsafe {
+            XGetErrorDatabaseText(
+                display,
+                c"XRequest".as_ptr(),
+                opcode.as_ptr(),
+                c"unknown request".as_ptr(),
+                request.as_mut_ptr(),
+                request.len() as i32,
+            );
+        }
+        let request = unsafe { CStr::from_ptr(request.as_ptr()) }.to_string_lossy();
+
+        error!(
+            "X Error: {} in {} (opcode {}.{}) on resource {:#x}",
+            text, request, e.request_code, e.minor_code, e.resourceid
+        );
 
         0
     }
@@ -536,6 +4162,9 @@ impl WindowManager {
 
 impl Drop for WindowManager {
     fn drop(&mut self) {
+        if let Some((_, path)) = &self.ipc_listener {
+            let _ = fs::remove_file(path);
+        }
         unsafe { XCloseDisplay(self.display.as_ptr()) };
     }
 }
@@ -0,0 +1,85 @@
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use x11::xlib::Window;
+
+/// A menu program listing the windows for Mod+W, whose choice is focused once it exits
+pub struct WindowMenu {
+    child: Child,
+    windows: Vec<Window>,
+}
+
+impl WindowMenu {
+    /// Runs `command` through `sh` with a numbered line per entry on its stdin
+    pub fn open(command: &str, entries: Vec<(Window, String)>) -> io::Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let input = format_entries(entries.iter().map(|(_, title)| title.as_str()));
+        if let Some(mut stdin) = child.stdin.take() {
+            // A menu that stops reading its input mustn't freeze the window manager
+            thread::spawn(move || stdin.write_all(input.as_bytes()));
+        }
+
+        Ok(WindowMenu {
+            child,
+            windows: entries.into_iter().map(|(w, _)| w).collect(),
+        })
+    }
+
+    /// Readable once the menu has printed its choice or exited without one
+    pub fn fd(&self) -> Option<RawFd> {
+        self.child.stdout.as_ref().map(|stdout| stdout.as_raw_fd())
+    }
+
+    /// Waits for the menu to exit and returns the window that was picked, if any
+    pub fn finish(mut self) -> Option<Window> {
+        let mut output = String::new();
+        if let Some(mut stdout) = self.child.stdout.take() {
+            let _ = stdout.read_to_string(&mut output);
+        }
+        let _ = self.child.wait();
+        parse_selection(&output).and_then(|i| self.windows.get(i).copied())
+    }
+}
+
+/// One line per title, numbered from 1 so the choice still maps to a window when titles repeat
+fn format_entries<'a>(titles: impl Iterator<Item = &'a str>) -> String {
+    titles
+        .enumerate()
+        .map(|(i, title)| format!("{}: {}\n", i + 1, title.replace('\n', " ")))
+        .collect()
+}
+
+/// The index of the entry a line printed by the menu starts with
+fn parse_selection(output: &str) -> Option<usize> {
+    let (number, _) = output.trim_start().split_once(':')?;
+    number.parse::<usize>().ok()?.checked_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_numbered_lines() {
+        assert_eq!(
+            format_entries(["vim", "two\nlines"].into_iter()),
+            "1: vim\n2: two lines\n"
+        );
+    }
+
+    #[test]
+    fn selections_map_back_to_entries() {
+        assert_eq!(parse_selection("2: two lines\n"), Some(1));
+        assert_eq!(parse_selection("1: a: b"), Some(0));
+        assert_eq!(parse_selection(""), None);
+        assert_eq!(parse_selection("typed text"), None);
+        assert_eq!(parse_selection("0: nothing"), None);
+    }
+}
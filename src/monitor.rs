@@ -0,0 +1,366 @@
+use crate::layout::Geometry;
+use log::{info, warn};
+use std::ffi::{c_void, CStr};
+use std::os::raw::{c_int, c_short, c_ulong};
+use std::sync::OnceLock;
+use x11::xlib::{Atom, Bool, Display, Window, XFree};
+
+/// A monitor and the workspace it shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Monitor {
+    pub geometry: Geometry,
+    /// Set on the monitor RandR reports as the primary one, which `primary_monitor` can override
+    pub primary: bool,
+    pub workspace: usize,
+}
+
+// RandR and Xinerama are loaded at runtime rather than linked, so the window manager still starts,
+// with a single monitor, where neither library is installed
+#[repr(C)]
+struct XRRMonitorInfo {
+    name: Atom,
+    primary: Bool,
+    automatic: Bool,
+    noutput: c_int,
+    x: c_int,
+    y: c_int,
+    width: c_int,
+    height: c_int,
+    mwidth: c_int,
+    mheight: c_int,
+    outputs: *mut c_ulong,
+}
+
+#[repr(C)]
+struct XineramaScreenInfo {
+    screen_number: c_int,
+    x_org: c_short,
+    y_org: c_short,
+    width: c_short,
+    height: c_short,
+}
+
+struct Randr {
+    get_monitors:
+        unsafe extern "C" fn(*mut Display, Window, Bool, *mut c_int) -> *mut XRRMonitorInfo,
+    free_monitors: unsafe extern "C" fn(*mut XRRMonitorInfo),
+}
+
+struct Xinerama {
+    is_active: unsafe extern "C" fn(*mut Display) -> Bool,
+    query_screens: unsafe extern "C" fn(*mut Display, *mut c_int) -> *mut XineramaScreenInfo,
+}
+
+static RANDR: OnceLock<Option<Randr>> = OnceLock::new();
+static XINERAMA: OnceLock<Option<Xinerama>> = OnceLock::new();
+
+/// Loads `library` for good. It is never closed, since Xlib keeps pointers into an extension's
+/// library once the extension was used on a connection.
+fn open(library: &CStr) -> Option<*mut c_void> {
+    let handle = unsafe { libc::dlopen(library.as_ptr(), libc::RTLD_LAZY) };
+    if handle.is_null() {
+        info!("{:?} is not available", library);
+        return None;
+    }
+    Some(handle)
+}
+
+/// The function `name` of the library `handle`, which must have the type `F`
+unsafe fn symbol<F: Copy>(handle: *mut c_void, name: &CStr) -> Option<F> {
+    let function = libc::dlsym(handle, name.as_ptr());
+    (!function.is_null()).then(|| std::mem::transmute_copy(&function))
+}
+
+fn randr() -> Option<&'static Randr> {
+    RANDR
+        .get_or_init(|| {
+            let handle = open(c"libXrandr.so.2")?;
+            unsafe {
+                Some(Randr {
+                    get_monitors: symbol(handle, c"XRRGetMonitors")?,
+                    free_monitors: symbol(handle, c"XRRFreeMonitors")?,
+                })
+            }
+        })
+        .as_ref()
+}
+
+fn xinerama() -> Option<&'static Xinerama> {
+    XINERAMA
+        .get_or_init(|| {
+            let handle = open(c"libXinerama.so.1")?;
+            unsafe {
+                Some(Xinerama {
+                    is_active: symbol(handle, c"XineramaIsActive")?,
+                    query_screens: symbol(handle, c"XineramaQueryScreens")?,
+                })
+            }
+        })
+        .as_ref()
+}
+
+/// The monitors of the screen as (geometry, primary) pairs, from RandR 1.5 or else Xinerama.
+/// Without either the whole `screen` is a single monitor.
+pub fn query(
+    display: *mut Display,
+    root: Window,
+    (width, height): (u32, u32),
+) -> Vec<(Geometry, bool)> {
+    let monitors = query_randr(display, root)
+        .filter(|monitors| !monitors.is_empty())
+        .or_else(|| query_xinerama(display).filter(|monitors| !monitors.is_empty()));
+    match monitors {
+        Some(monitors) => dedup(monitors),
+        None => vec![(
+            Geometry {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+            true,
+        )],
+    }
+}
+
+fn query_randr(display: *mut Display, root: Window) -> Option<Vec<(Geometry, bool)>> {
+    let randr = randr()?;
+    let mut count = 0;
+    // Servers older than RandR 1.5 have no monitors to report
+    let monitors = unsafe { (randr.get_monitors)(display, root, 1, &mut count) };
+    if monitors.is_null() {
+        return None;
+    }
+    let found = unsafe { std::slice::from_raw_parts(monitors, count.max(0) as usize) }
+        .iter()
+        .filter(|m| m.width > 0 && m.height > 0)
+        .map(|m| {
+            let geometry = Geometry {
+                x: m.x,
+                y: m.y,
+                width: m.width as u32,
+                height: m.height as u32,
+            };
+            (geometry, m.primary != 0)
+        })
+        .collect();
+    unsafe { (randr.free_monitors)(monitors) };
+    Some(found)
+}
+
+fn query_xinerama(display: *mut Display) -> Option<Vec<(Geometry, bool)>> {
+    let xinerama = xinerama()?;
+    if unsafe { (xinerama.is_active)(display) } == 0 {
+        return None;
+    }
+    let mut count = 0;
+    let screens = unsafe { (xinerama.query_screens)(display, &mut count) };
+    if screens.is_null() {
+        return None;
+    }
+    let found = unsafe { std::slice::from_raw_parts(screens, count.max(0) as usize) }
+        .iter()
+        .filter(|s| s.width > 0 && s.height > 0)
+        .map(|s| {
+            let geometry = Geometry {
+                x: s.x_org as i32,
+                y: s.y_org as i32,
+                width: s.width as u32,
+                height: s.height as u32,
+            };
+            (geometry, false)
+        })
+        .collect();
+    unsafe { XFree(screens as *mut c_void) };
+    Some(found)
+}
+
+/// Drops monitors with the same geometry as an earlier one, which mirror it
+fn dedup(monitors: Vec<(Geometry, bool)>) -> Vec<(Geometry, bool)> {
+    let mut unique: Vec<(Geometry, bool)> = Vec::with_capacity(monitors.len());
+    for (geometry, primary) in monitors {
+        match unique.iter_mut().find(|(g, _)| *g == geometry) {
+            Some((_, p)) => *p |= primary,
+            None => unique.push((geometry, primary)),
+        }
+    }
+    unique
+}
+
+/// Gives each of the monitors in `found` a workspace of its own out of `workspaces`. Monitors keep
+/// the workspace they showed in `previous`, or else the one shown at the same index, and new ones
+/// get the lowest one not shown yet. Monitors beyond the number of workspaces are left out, since
+/// they would have nothing to show.
+pub fn arrange(
+    found: Vec<(Geometry, bool)>,
+    previous: &[Monitor],
+    workspaces: usize,
+) -> Vec<Monitor> {
+    if found.len() > workspaces {
+        warn!(
+            "Only using {} of {} monitors, one per workspace",
+            workspaces,
+            found.len()
+        );
+    }
+    let mut monitors: Vec<Monitor> = Vec::new();
+    for (i, (geometry, primary)) in found.into_iter().take(workspaces).enumerate() {
+        let taken = |workspace: usize| monitors.iter().any(|m| m.workspace == workspace);
+        let workspace = previous
+            .iter()
+            .find(|m| m.geometry == geometry)
+            .or(previous.get(i))
+            .map(|m| m.workspace)
+            .filter(|&workspace| workspace < workspaces && !taken(workspace))
+            .or_else(|| (0..workspaces).find(|&workspace| !taken(workspace)))
+            .unwrap();
+        monitors.push(Monitor {
+            geometry,
+            primary,
+            workspace,
+        });
+    }
+    monitors
+}
+
+/// The index of the primary monitor, which is `forced` if there is such a monitor, or else the one
+/// RandR reports, or else the first one
+pub fn primary(monitors: &[Monitor], forced: Option<usize>) -> usize {
+    forced
+        .filter(|&i| i < monitors.len())
+        .or_else(|| monitors.iter().position(|m| m.primary))
+        .unwrap_or(0)
+}
+
+/// One line per monitor with its index, geometry and workspace, marking the `primary` and
+/// `focused` ones
+pub fn describe(monitors: &[Monitor], primary: usize, focused: usize) -> String {
+    monitors
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let g = m.geometry;
+            format!(
+                "{} {}x{}+{}+{} workspace {}{}{}\n",
+                i,
+                g.width,
+                g.height,
+                g.x,
+                g.y,
+                m.workspace,
+                if i == primary { " primary" } else { "" },
+                if i == focused { " focused" } else { "" },
+            )
+        })
+        .collect()
+}
+
+/// The index of the monitor (`x`, `y`) is on, or of the first one if it is on none
+pub fn at(monitors: &[Monitor], x: i32, y: i32) -> usize {
+    monitors
+        .iter()
+        .position(|m| m.geometry.contains(x, y))
+        .unwrap_or(0)
+}
+
+/// The index of the monitor most of `geometry` is on, or of the first one if it is on none
+pub fn most_overlapping(monitors: &[Monitor], geometry: &Geometry) -> usize {
+    monitors
+        .iter()
+        .enumerate()
+        .max_by_key(|&(i, m)| (m.geometry.overlap(geometry), std::cmp::Reverse(i)))
+        .map_or(0, |(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(x: i32, y: i32, width: u32, height: u32) -> Geometry {
+        Geometry {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn monitors(geometries: &[Geometry]) -> Vec<Monitor> {
+        let found = geometries.iter().map(|&g| (g, false)).collect();
+        arrange(found, &[], geometries.len())
+    }
+
+    #[test]
+    fn mirrored_outputs_are_one_monitor() {
+        let left = geometry(0, 0, 1920, 1080);
+        let right = geometry(1920, 0, 1280, 1024);
+        assert_eq!(
+            dedup(vec![(left, false), (right, false), (left, true)]),
+            vec![(left, true), (right, false)]
+        );
+    }
+
+    #[test]
+    fn monitors_keep_their_workspaces_when_rearranged() {
+        let left = geometry(0, 0, 1920, 1080);
+        let right = geometry(1920, 0, 1280, 1024);
+        let mut previous = monitors(&[left, right]);
+        assert_eq!(previous[0].workspace, 0);
+        assert_eq!(previous[1].workspace, 1);
+        previous[0].workspace = 4;
+
+        // A third monitor gets the lowest workspace not shown on the others
+        let third = geometry(3200, 0, 800, 600);
+        let found = vec![(left, false), (right, true), (third, false)];
+        let arranged = arrange(found, &previous, 5);
+        let workspaces: Vec<usize> = arranged.iter().map(|m| m.workspace).collect();
+        assert_eq!(workspaces, [4, 1, 0]);
+        assert!(arranged[1].primary);
+
+        // Unplugging one leaves the others alone, and extra monitors without a workspace are unused
+        assert_eq!(arrange(vec![(right, false)], &arranged, 5)[0].workspace, 1);
+        // A monitor changing its resolution keeps its place
+        let smaller = geometry(0, 0, 1280, 720);
+        assert_eq!(
+            arrange(vec![(smaller, false)], &arranged, 5)[0].workspace,
+            4
+        );
+        assert_eq!(
+            arrange(vec![(left, false), (right, false)], &[], 1).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn windows_belong_to_the_monitor_they_overlap_most() {
+        let monitors = monitors(&[geometry(0, 0, 1920, 1080), geometry(1920, 0, 1280, 1024)]);
+        assert_eq!(at(&monitors, 100, 100), 0);
+        assert_eq!(at(&monitors, 2000, 100), 1);
+        // Below the shorter monitor
+        assert_eq!(at(&monitors, 2000, 1050), 0);
+
+        assert_eq!(most_overlapping(&monitors, &geometry(1800, 0, 400, 300)), 1);
+        assert_eq!(most_overlapping(&monitors, &geometry(1700, 0, 400, 300)), 0);
+        // Off every monitor
+        assert_eq!(most_overlapping(&monitors, &geometry(-500, 0, 100, 100)), 0);
+    }
+
+    #[test]
+    fn the_primary_monitor_can_be_overridden() {
+        let found = vec![
+            (geometry(0, 0, 1920, 1080), false),
+            (geometry(1920, 0, 1280, 1024), true),
+        ];
+        let monitors = arrange(found, &[], 9);
+        assert_eq!(primary(&monitors, None), 1);
+        assert_eq!(primary(&monitors, Some(0)), 0);
+        // Forcing a monitor that isn't plugged in leaves it to RandR
+        assert_eq!(primary(&monitors, Some(2)), 1);
+        assert_eq!(primary(&monitors[..1], None), 0);
+
+        assert_eq!(
+            describe(&monitors, 1, 0),
+            "0 1920x1080+0+0 workspace 0 focused\n1 1280x1024+1920+0 workspace 1 primary\n"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::ffi::CStr;
+use std::fs;
+use std::io;
+use std::os::raw::c_char;
+
+/// The parent processes of `pid`, closest first, as far as they can be read from `/proc`
+pub fn ancestors(pid: u32) -> Vec<u32> {
+    let mut ancestors = Vec::new();
+    let mut pid = pid;
+    // init has no parent worth looking at
+    while let Some(parent) = parent_pid(pid).filter(|&parent| parent > 1) {
+        ancestors.push(parent);
+        pid = parent;
+    }
+    ancestors
+}
+
+fn parent_pid(pid: u32) -> Option<u32> {
+    parse_parent_pid(&fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+}
+
+/// Reads the parent pid from the contents of `/proc/<pid>/stat`
+fn parse_parent_pid(stat: &str) -> Option<u32> {
+    // The command name is in parentheses and may itself contain spaces or parentheses, and it is
+    // followed by the state and then the parent pid
+    let fields = &stat[stat.rfind(')')? + 1..];
+    fields.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// The name of this machine, to tell whether a window's process runs here
+pub fn hostname() -> Option<String> {
+    let mut name = [0 as c_char; 256];
+    if unsafe { libc::gethostname(name.as_mut_ptr(), name.len()) } != 0 {
+        return None;
+    }
+    // The name isn't terminated if it was truncated
+    *name.last_mut()? = 0;
+    let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// Sends SIGKILL to `pid`, which can't be caught or ignored
+pub fn force_kill(pid: u32) -> io::Result<()> {
+    // Negative or zero pids would signal whole process groups
+    let pid = libc::pid_t::try_from(pid)
+        .ok()
+        .filter(|&pid| pid > 1)
+        .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+    if unsafe { libc::kill(pid, libc::SIGKILL) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_parent_pid() {
+        assert_eq!(
+            parse_parent_pid("1234 (mpv) S 1200 1234 1200 0"),
+            Some(1200)
+        );
+    }
+
+    #[test]
+    fn command_names_can_contain_spaces_and_parentheses() {
+        assert_eq!(parse_parent_pid("42 (a (weird) name) R 7 42"), Some(7));
+    }
+
+    #[test]
+    fn malformed_stat_has_no_parent() {
+        assert_eq!(parse_parent_pid(""), None);
+        assert_eq!(parse_parent_pid("42 (sh) S"), None);
+    }
+}
@@ -0,0 +1,89 @@
+use std::ffi::{CStr, CString};
+use std::ptr;
+use x11::xlib::{
+    Display, XResourceManagerString, XrmDatabase, XrmDestroyDatabase, XrmGetResource,
+    XrmGetStringDatabase, XrmInitialize, XrmValue,
+};
+
+/// The X resources loaded with xrdb, as they were when this was created
+pub struct Resources {
+    /// Null if no resources were loaded
+    database: XrmDatabase,
+}
+
+impl Resources {
+    pub fn load(display: *mut Display) -> Self {
+        let database = unsafe {
+            XrmInitialize();
+            let string = XResourceManagerString(display);
+            if string.is_null() {
+                ptr::null_mut()
+            } else {
+                XrmGetStringDatabase(string)
+            }
+        };
+        Resources { database }
+    }
+
+    /// The value of the resource `wm-rs.<name>`
+    pub fn get(&self, name: &str) -> Option<String> {
+        if self.database.is_null() {
+            return None;
+        }
+        let full_name = CString::new(format!("wm-rs.{}", name)).ok()?;
+        let class = CString::new(format!("Wm-rs.{}", capitalize(name))).ok()?;
+
+        let mut value_type = ptr::null_mut();
+        let mut value = XrmValue {
+            size: 0,
+            addr: ptr::null_mut(),
+        };
+        let found = unsafe {
+            XrmGetResource(
+                self.database,
+                full_name.as_ptr(),
+                class.as_ptr(),
+                &mut value_type,
+                &mut value,
+            )
+        } != 0;
+        if !found || value.addr.is_null() {
+            return None;
+        }
+        Some(
+            unsafe { CStr::from_ptr(value.addr) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+}
+
+impl Drop for Resources {
+    fn drop(&mut self) {
+        if !self.database.is_null() {
+            unsafe {
+                XrmDestroyDatabase(self.database);
+            }
+        }
+    }
+}
+
+/// Resource classes are spelled like their names with the first letter capitalized
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classes_are_capitalized_names() {
+        assert_eq!(capitalize("borderColor"), "BorderColor");
+        assert_eq!(capitalize(""), "");
+    }
+}
@@ -0,0 +1,118 @@
+use std::os::raw::c_int;
+use x11::xlib::{
+    Bool, Display, Pixmap, Window, XCreateGC, XCreatePixmap, XFillArc, XFillRectangle, XFreeGC,
+    XFreePixmap, XRectangle, XSetForeground,
+};
+
+// The x11 crate has no bindings for the Shape extension, which lives in libXext
+#[link(name = "Xext")]
+extern "C" {
+    fn XShapeQueryExtension(
+        display: *mut Display,
+        event_base: *mut c_int,
+        error_base: *mut c_int,
+    ) -> Bool;
+    fn XShapeCombineMask(
+        display: *mut Display,
+        dest: Window,
+        dest_kind: c_int,
+        x_off: c_int,
+        y_off: c_int,
+        src: Pixmap,
+        op: c_int,
+    );
+    fn XShapeCombineRectangles(
+        display: *mut Display,
+        dest: Window,
+        dest_kind: c_int,
+        x_off: c_int,
+        y_off: c_int,
+        rectangles: *mut XRectangle,
+        n_rects: c_int,
+        op: c_int,
+        ordering: c_int,
+    );
+}
+
+const SHAPE_SET: c_int = 0;
+const SHAPE_BOUNDING: c_int = 0;
+const SHAPE_INPUT: c_int = 2;
+const UNSORTED: c_int = 0;
+
+pub fn is_supported(display: *mut Display) -> bool {
+    let mut event_base = 0;
+    let mut error_base = 0;
+    unsafe { XShapeQueryExtension(display, &mut event_base, &mut error_base) != 0 }
+}
+
+/// Cuts the corners of `w`, a `width`x`height` window with a `border_width` border, into quarter
+/// circles of `radius`. A radius of 0 gives the window back its square corners.
+pub fn round_corners(
+    display: *mut Display,
+    w: Window,
+    width: u32,
+    height: u32,
+    border_width: u32,
+    radius: u32,
+) {
+    // The bounding shape includes the border
+    let width = width + 2 * border_width;
+    let height = height + 2 * border_width;
+    let radius = radius.min(width / 2).min(height / 2);
+    let diameter = 2 * radius;
+
+    unsafe {
+        if radius == 0 {
+            XShapeCombineMask(display, w, SHAPE_BOUNDING, 0, 0, 0, SHAPE_SET);
+            return;
+        }
+
+        let mask = XCreatePixmap(display, w, width, height, 1);
+        let gc = XCreateGC(display, mask, 0, std::ptr::null_mut());
+
+        XSetForeground(display, gc, 0);
+        XFillRectangle(display, mask, gc, 0, 0, width, height);
+
+        XSetForeground(display, gc, 1);
+        let (r, right, bottom) = (
+            radius as i32,
+            (width - diameter) as i32,
+            (height - diameter) as i32,
+        );
+        for (x, y) in [(0, 0), (right, 0), (0, bottom), (right, bottom)] {
+            XFillArc(display, mask, gc, x, y, diameter, diameter, 0, 360 * 64);
+        }
+        XFillRectangle(display, mask, gc, r, 0, width - diameter, height);
+        XFillRectangle(display, mask, gc, 0, r, width, height - diameter);
+
+        XShapeCombineMask(
+            display,
+            w,
+            SHAPE_BOUNDING,
+            -(border_width as i32),
+            -(border_width as i32),
+            mask,
+            SHAPE_SET,
+        );
+
+        XFreeGC(display, gc);
+        XFreePixmap(display, mask);
+    }
+}
+
+/// Gives `w` an empty input shape, so clicks fall through to the windows below it
+pub fn clear_input(display: *mut Display, w: Window) {
+    unsafe {
+        XShapeCombineRectangles(
+            display,
+            w,
+            SHAPE_INPUT,
+            0,
+            0,
+            std::ptr::null_mut(),
+            0,
+            SHAPE_SET,
+            UNSORTED,
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::colors;
+use crate::config::Color;
+use crate::focus;
+use crate::layout::Geometry;
+use std::mem::MaybeUninit;
+use x11::xlib::{
+    CWBackPixel, CWOverrideRedirect, CopyFromParent, Display, ExposureMask, Window, XClearWindow,
+    XCreateGC, XCreateWindow, XDestroyWindow, XDrawString, XFillRectangle, XFreeGC, XMapRaised,
+    XSelectInput, XSetForeground, XSetWindowAttributes, GC,
+};
+
+const WIDTH: u32 = 400;
+const LINE_HEIGHT: u32 = 20;
+const PADDING: i32 = 5;
+const BACKGROUND: u32 = 0x222222;
+const TEXT: u32 = 0xFFFFFF;
+
+/// An overlay listing the windows being cycled through with Mod+Tab, of which the selected one is
+/// only focused once the overlay is closed
+pub struct Switcher {
+    window: Window,
+    gc: GC,
+    entries: Vec<(Window, String)>,
+    selected: usize,
+    highlight: u64,
+    text: u64,
+}
+
+impl Switcher {
+    /// Shows the titles of `entries` in the middle of the `area`, with the one `delta` steps away
+    /// from `current` selected in `highlight`
+    pub fn open(
+        display: *mut Display,
+        root: Window,
+        area: Geometry,
+        entries: Vec<(Window, String)>,
+        current: Option<usize>,
+        delta: isize,
+        highlight: u64,
+    ) -> Self {
+        let height = entries.len() as u32 * LINE_HEIGHT;
+        let x = area.x + (area.width as i32 - WIDTH as i32) / 2;
+        let y = area.y + (area.height as i32 - height as i32) / 2;
+
+        let (window, gc) = unsafe {
+            let mut attributes: XSetWindowAttributes = MaybeUninit::zeroed().assume_init();
+            // The window manager must not try to frame its own overlay
+            attributes.override_redirect = 1;
+            attributes.background_pixel = colors::pixel(display, &Color::Rgb(BACKGROUND));
+            let window = XCreateWindow(
+                display,
+                root,
+                x,
+                y,
+                WIDTH,
+                height.max(1),
+                0,
+                CopyFromParent,
+                CopyFromParent as u32,
+                std::ptr::null_mut(),
+                CWOverrideRedirect | CWBackPixel,
+                &mut attributes,
+            );
+            XSelectInput(display, window, ExposureMask);
+            XMapRaised(display, window);
+            (window, XCreateGC(display, window, 0, std::ptr::null_mut()))
+        };
+
+        let selected = focus::relative_index(entries.len(), current, delta);
+        Switcher {
+            window,
+            gc,
+            entries,
+            selected,
+            highlight,
+            text: colors::pixel(display, &Color::Rgb(TEXT)),
+        }
+    }
+
+    pub fn window(&self) -> Window {
+        self.window
+    }
+
+    pub fn selected(&self) -> Window {
+        self.entries[self.selected].0
+    }
+
+    pub fn select_relative(&mut self, display: *mut Display, delta: isize) {
+        self.selected = focus::relative_index(self.entries.len(), Some(self.selected), delta);
+        self.draw(display);
+    }
+
+    pub fn draw(&self, display: *mut Display) {
+        unsafe {
+            XClearWindow(display, self.window);
+            for (i, (_, title)) in self.entries.iter().enumerate() {
+                let top = (i as u32 * LINE_HEIGHT) as i32;
+                if i == self.selected {
+                    XSetForeground(display, self.gc, self.highlight);
+                    XFillRectangle(display, self.window, self.gc, 0, top, WIDTH, LINE_HEIGHT);
+                }
+                XSetForeground(display, self.gc, self.text);
+                XDrawString(
+                    display,
+                    self.window,
+                    self.gc,
+                    PADDING,
+                    top + LINE_HEIGHT as i32 - PADDING,
+                    title.as_ptr() as *const _,
+                    title.len() as i32,
+                );
+            }
+        }
+    }
+
+    pub fn close(self, display: *mut Display) {
+        unsafe {
+            XFreeGC(display, self.gc);
+            XDestroyWindow(display, self.window);
+        }
+    }
+}
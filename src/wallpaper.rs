@@ -0,0 +1,116 @@
+use crate::colors;
+use crate::config::Color;
+use log::{info, warn};
+use std::ffi::CString;
+use std::mem::MaybeUninit;
+use std::os::raw::c_ulong;
+use std::ptr::{self, NonNull};
+use x11::xlib::{
+    AnyPropertyType, Atom, Display, False, Pixmap, PropModeReplace, RetainPermanent, Window,
+    XChangeProperty, XClearWindow, XCloseDisplay, XCreateGC, XCreatePixmap, XDefaultDepth,
+    XDefaultRootWindow, XDefaultScreen, XDisplayHeight, XDisplayString, XDisplayWidth,
+    XFillRectangle, XFree, XFreeGC, XGetWindowProperty, XInternAtom, XKillClient, XOpenDisplay,
+    XSetCloseDownMode, XSetForeground, XSetWindowBackgroundPixmap, XA_PIXMAP,
+};
+
+/// Fills the root window with `color` and publishes the pixmap through _XROOTPMAP_ID and
+/// ESETROOT_PMAP_ID, which pseudo-transparent terminals draw their background from.
+///
+/// Like other wallpaper setters the pixmap is made on a connection of its own that is closed
+/// with its resources kept, so the next setter can free it with XKillClient without taking the
+/// window manager down with it.
+pub fn set(display: *mut Display, color: &Color) {
+    let own = match NonNull::new(unsafe { XOpenDisplay(XDisplayString(display)) }) {
+        Some(own) => own.as_ptr(),
+        None => {
+            warn!("Failed to open a connection for setting the wallpaper");
+            return;
+        }
+    };
+
+    unsafe {
+        let screen = XDefaultScreen(own);
+        let root = XDefaultRootWindow(own);
+        let width = XDisplayWidth(own, screen) as u32;
+        let height = XDisplayHeight(own, screen) as u32;
+        let pixmap = XCreatePixmap(own, root, width, height, XDefaultDepth(own, screen) as u32);
+        let gc = XCreateGC(own, pixmap, 0, ptr::null_mut());
+        XSetForeground(own, gc, colors::pixel(own, color));
+        XFillRectangle(own, pixmap, gc, 0, 0, width, height);
+        XFreeGC(own, gc);
+
+        let root_pmap = intern(own, "_XROOTPMAP_ID");
+        let esetroot_pmap = intern(own, "ESETROOT_PMAP_ID");
+        free_previous(own, root, root_pmap, esetroot_pmap);
+        for property in [root_pmap, esetroot_pmap] {
+            XChangeProperty(
+                own,
+                root,
+                property,
+                XA_PIXMAP,
+                32,
+                PropModeReplace,
+                &pixmap as *const Pixmap as *const u8,
+                1,
+            );
+        }
+        XSetWindowBackgroundPixmap(own, root, pixmap);
+        XClearWindow(own, root);
+
+        XSetCloseDownMode(own, RetainPermanent);
+        XCloseDisplay(own);
+    }
+    info!("Set the wallpaper to {:?}", color);
+}
+
+/// Frees the pixmap left by the last wallpaper setter, which is only known to be safe when both
+/// properties still name the same one
+fn free_previous(display: *mut Display, root: Window, root_pmap: Atom, esetroot_pmap: Atom) {
+    if let Some(previous) = get_pixmap(display, root, root_pmap) {
+        if get_pixmap(display, root, esetroot_pmap) == Some(previous) {
+            unsafe { XKillClient(display, previous) };
+        }
+    }
+}
+
+fn get_pixmap(display: *mut Display, w: Window, property: Atom) -> Option<Pixmap> {
+    let mut actual_type = 0;
+    let mut actual_format = 0;
+    let mut count: c_ulong = 0;
+    let mut bytes_after: c_ulong = 0;
+    let mut data = MaybeUninit::<*mut u8>::uninit();
+    let status = unsafe {
+        XGetWindowProperty(
+            display,
+            w,
+            property,
+            0,
+            1,
+            False,
+            AnyPropertyType as Atom,
+            &mut actual_type,
+            &mut actual_format,
+            &mut count,
+            &mut bytes_after,
+            data.as_mut_ptr(),
+        )
+    };
+    if status != 0 {
+        return None;
+    }
+    let data = unsafe { data.assume_init() };
+    if data.is_null() {
+        return None;
+    }
+    // Format 32 properties are handed out as longs
+    let pixmap = (actual_type == XA_PIXMAP && actual_format == 32 && count == 1)
+        .then(|| unsafe { *(data as *const Pixmap) })
+        .filter(|&pixmap| pixmap != 0);
+    unsafe { XFree(data as *mut _) };
+    pixmap
+}
+
+fn intern(display: *mut Display, name: &str) -> Atom {
+    let name = CString::new(name).unwrap();
+    unsafe { XInternAtom(display, name.as_ptr(), False) }
+}